@@ -0,0 +1,164 @@
+// A bitboard has one bit per square, using the same indexes as Board::pieces,
+// so bit 0 is the top left square and bit 63 the bottom right.
+pub type Bitboard = u64;
+
+pub const fn square_bb(index: i32) -> Bitboard {
+    1 << index
+}
+
+// Iterates over the indexes of the set bits, lowest first
+pub struct Indexes(pub Bitboard);
+
+impl Iterator for Indexes {
+    type Item = i32;
+
+    fn next(&mut self) -> Option<i32> {
+        if self.0 == 0 {
+            None
+        } else {
+            let index = self.0.trailing_zeros() as i32;
+            self.0 &= self.0 - 1;
+            Some(index)
+        }
+    }
+}
+
+// Squares where (row + column) is even, like the top left square
+pub const LIGHT_SQUARES: Bitboard = 0xAA55_AA55_AA55_AA55;
+
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [(2, 1), (2, -1), (1, 2), (1, -2), (-1, 2), (-1, -2), (-2, 1), (-2, -1)];
+const KING_OFFSETS: [(i32, i32); 8] = [(1, 1), (1, 0), (1, -1), (0, 1), (0, -1), (-1, 1), (-1, 0), (-1, -1)];
+// White pawns move towards row 0, black pawns towards row 7
+const WHITE_PAWN_OFFSETS: [(i32, i32); 2] = [(-1, 1), (-1, -1)];
+const BLACK_PAWN_OFFSETS: [(i32, i32); 2] = [(1, 1), (1, -1)];
+
+pub const KNIGHT_ATTACKS: [Bitboard; 64] = offset_table(&KNIGHT_OFFSETS);
+pub const KING_ATTACKS: [Bitboard; 64] = offset_table(&KING_OFFSETS);
+// Indexed by colour of the attacking pawn, then its square
+pub const PAWN_ATTACKS: [[Bitboard; 64]; 2] = [offset_table(&WHITE_PAWN_OFFSETS), offset_table(&BLACK_PAWN_OFFSETS)];
+
+// Every square on the same line through each square, not including the square itself
+const FILE_MASKS: [Bitboard; 64] = line_table(1, 0);
+const RANK_MASKS: [Bitboard; 64] = line_table(0, 1);
+const DIAGONAL_MASKS: [Bitboard; 64] = line_table(1, 1);
+const ANTI_DIAGONAL_MASKS: [Bitboard; 64] = line_table(1, -1);
+
+const fn on_board(row: i32, column: i32) -> bool {
+    row >= 0 && row < 8 && column >= 0 && column < 8
+}
+
+const fn offset_table(offsets: &[(i32, i32)]) -> [Bitboard; 64] {
+    let mut table = [0; 64];
+    let mut index = 0;
+    while index < 64 {
+        let mut i = 0;
+        while i < offsets.len() {
+            let row = index / 8 + offsets[i].0;
+            let column = index % 8 + offsets[i].1;
+            if on_board(row, column) {
+                table[index as usize] |= square_bb(row * 8 + column);
+            }
+            i += 1;
+        }
+        index += 1;
+    }
+    table
+}
+
+const fn line_table(row_step: i32, column_step: i32) -> [Bitboard; 64] {
+    let mut table = [0; 64];
+    let mut index = 0;
+    while index < 64 {
+        // Walk out from the square in both directions along the line
+        let mut direction = -1;
+        while direction <= 1 {
+            let mut row = index / 8 + row_step * direction;
+            let mut column = index % 8 + column_step * direction;
+            while on_board(row, column) {
+                table[index as usize] |= square_bb(row * 8 + column);
+                row += row_step * direction;
+                column += column_step * direction;
+            }
+            direction += 2;
+        }
+        index += 1;
+    }
+    table
+}
+
+// Hyperbola quintessence: subtracting the slider from the blockers on a line flips every bit up to
+// and including the first blocker above it. Doing the same on the bit reversed board finds the
+// first blocker below it, and together they give the attacked squares in both directions.
+fn line_attacks(index: i32, occupied: Bitboard, mask: Bitboard) -> Bitboard {
+    let slider = square_bb(index);
+    let blockers = occupied & mask;
+
+    let forward = blockers.wrapping_sub(slider);
+    let reverse = blockers.reverse_bits().wrapping_sub(slider.reverse_bits()).reverse_bits();
+
+    (forward ^ reverse) & mask
+}
+
+pub fn bishop_attacks(index: i32, occupied: Bitboard) -> Bitboard {
+    line_attacks(index, occupied, DIAGONAL_MASKS[index as usize])
+        | line_attacks(index, occupied, ANTI_DIAGONAL_MASKS[index as usize])
+}
+
+pub fn rook_attacks(index: i32, occupied: Bitboard) -> Bitboard {
+    line_attacks(index, occupied, FILE_MASKS[index as usize])
+        | line_attacks(index, occupied, RANK_MASKS[index as usize])
+}
+
+pub fn queen_attacks(index: i32, occupied: Bitboard) -> Bitboard {
+    bishop_attacks(index, occupied) | rook_attacks(index, occupied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Slow but obviously correct ray walk to compare the sliding attacks against
+    fn walk(index: i32, occupied: Bitboard, directions: &[(i32, i32)]) -> Bitboard {
+        let mut attacks = 0;
+        for (row_step, column_step) in directions {
+            let mut row = index / 8 + row_step;
+            let mut column = index % 8 + column_step;
+            while on_board(row, column) {
+                attacks |= square_bb(row * 8 + column);
+                if occupied & square_bb(row * 8 + column) != 0 {
+                    break;
+                }
+                row += row_step;
+                column += column_step;
+            }
+        }
+        attacks
+    }
+
+    #[test]
+    fn sliding_attacks() {
+        // Deterministic pseudo random occupancies
+        let mut seed: u64 = 0x9E37_79B9_7F4A_7C15;
+        for _ in 0..200 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let occupied = seed & (seed >> 3);
+
+            for index in 0..64 {
+                assert_eq!(bishop_attacks(index, occupied), walk(index, occupied, &[(1, 1), (1, -1), (-1, 1), (-1, -1)]));
+                assert_eq!(rook_attacks(index, occupied), walk(index, occupied, &[(1, 0), (-1, 0), (0, 1), (0, -1)]));
+            }
+        }
+    }
+
+    #[test]
+    fn leaper_attacks() {
+        // Knight in the corner, king in the middle
+        assert_eq!(Indexes(KNIGHT_ATTACKS[0]).collect::<Vec<i32>>(), vec![10, 17]);
+        assert_eq!(KING_ATTACKS[27].count_ones(), 8);
+        // White pawn on e2 attacks d3 and f3
+        assert_eq!(Indexes(PAWN_ATTACKS[0][52]).collect::<Vec<i32>>(), vec![43, 45]);
+        assert_eq!(LIGHT_SQUARES & 1, 1);
+    }
+}
@@ -0,0 +1,89 @@
+use crate::*;
+
+impl Board {
+    // Finds the legal move written in the coordinate notation used by engine protocols: the start
+    // and end squares, then a promotion piece if there is one, e.g. e2e4, e1g1 for castling or e7e8q.
+    // Move's Display writes moves the same way.
+    pub fn parse_coordinate(&self, notation: &str) -> Result<Move, MoveError> {
+        let notation = notation.trim();
+        if let Some(position) = notation.chars().position(|v| !v.is_ascii()) {
+            return Err(MoveError::notation(notation, position, "Only ascii characters can be used"));
+        }
+        if notation.len() < 4 || notation.len() > 5 {
+            return Err(MoveError::notation(notation, notation.len().min(5), "Expected a start and end square, e.g. e2e4"));
+        }
+
+        let start = parse_square_at(notation, 0)?;
+        let end = parse_square_at(notation, 2)?;
+        let promotion = match notation.chars().nth(4) {
+            Some(char) => Some(Piece::promotion_from_char(char.to_ascii_lowercase())
+                .ok_or_else(|| MoveError::notation(notation, 4, "Invalid promotion piece"))?),
+            None => None
+        };
+
+        let piece = match self.pieces[start.index as usize] {
+            Full(piece) => piece,
+            Empty => return Err(MoveError::EmptySquare(start)),
+        };
+        if piece.colour != self.turn {
+            return Err(MoveError::WrongTurn(self.turn));
+        }
+
+        let promotion = if Move::is_promotion(&piece, &end) {
+            Some(promotion.ok_or(MoveError::PromotionRequired)?)
+        } else if promotion.is_some() {
+            return Err(MoveError::InvalidPromotion);
+        } else {
+            None
+        };
+
+        let _move = Move { piece, start, end, promotion };
+        if !self.validate_move(_move) {
+            return Err(MoveError::CannotReach { piece: piece.variant, start: Some(start), end });
+        }
+        if !self.clone().is_legal(_move) {
+            return Err(MoveError::LeavesKingInCheck(self.turn));
+        }
+        Ok(_move)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Board, MoveError, Queen, Square, White};
+
+    #[test]
+    fn moves() {
+        let board = Board::new();
+        let _move = board.parse_coordinate("e2e4").unwrap();
+        assert_eq!(board.to_san(_move), "e4");
+        assert_eq!(_move.to_string(), "e2e4");
+
+        // Every legal move is written and read back the same way
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        for _move in board.legal_moves() {
+            assert_eq!(board.parse_coordinate(&_move.to_string()), Ok(_move));
+        }
+        assert_eq!(board.to_san(board.parse_coordinate("e1c1").unwrap()), "O-O-O");
+
+        let board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.parse_coordinate("b7b8q").unwrap().promotion, Some(Queen));
+        assert_eq!(board.to_san(board.parse_coordinate("b7b8N").unwrap()), "b8=N");
+    }
+
+    #[test]
+    fn errors() {
+        let square = |name: &str| name.parse::<Square>().unwrap();
+        let board = Board::new();
+        assert!(matches!(board.parse_coordinate("e2"), Err(MoveError::Notation { .. })));
+        assert!(matches!(board.parse_coordinate("e2e9"), Err(MoveError::Notation { position: 3, .. })));
+        assert_eq!(board.parse_coordinate("e3e4"), Err(MoveError::EmptySquare(square("e3"))));
+        assert_eq!(board.parse_coordinate("e7e5"), Err(MoveError::WrongTurn(White)));
+        assert_eq!(board.parse_coordinate("e2e4q"), Err(MoveError::InvalidPromotion));
+        assert!(matches!(board.parse_coordinate("e2e5"), Err(MoveError::CannotReach { .. })));
+
+        let board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K2r w - - 0 1").unwrap();
+        assert_eq!(board.parse_coordinate("b7b8"), Err(MoveError::PromotionRequired));
+        assert_eq!(board.parse_coordinate("b7b8q"), Err(MoveError::LeavesKingInCheck(White)));
+    }
+}
@@ -0,0 +1,468 @@
+use crate::*;
+use crate::bitboard::*;
+
+// Every term is scored twice, once for the middlegame and once for the endgame, and the two are
+// blended by how much material is left. This lets e.g. the king hide in the middlegame but come
+// out to fight in the endgame, without the score jumping when a piece comes off.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+struct Score {
+    mg: i32,
+    eg: i32,
+}
+
+impl Score {
+    const fn new(mg: i32, eg: i32) -> Score {
+        Score { mg, eg }
+    }
+}
+
+impl std::ops::Add for Score {
+    type Output = Score;
+
+    fn add(self, other: Score) -> Score {
+        Score::new(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+impl std::ops::Sub for Score {
+    type Output = Score;
+
+    fn sub(self, other: Score) -> Score {
+        Score::new(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl std::ops::Mul<i32> for Score {
+    type Output = Score;
+
+    fn mul(self, count: i32) -> Score {
+        Score::new(self.mg * count, self.eg * count)
+    }
+}
+
+impl std::ops::AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        *self = *self + other;
+    }
+}
+
+// Indexed by Piece::index
+const MATERIAL: [Score; 6] = [
+    Score::new(82, 94),
+    Score::new(337, 281),
+    Score::new(365, 297),
+    Score::new(477, 512),
+    Score::new(1025, 936),
+    Score::new(0, 0),
+];
+
+// Phase is the weight of the middlegame score, out of MAX_PHASE with every piece on the board
+const PHASE: [i32; 6] = [0, 1, 1, 2, 4, 0];
+const MAX_PHASE: i32 = 24;
+
+// Piece-square tables, laid out like the board from white's side, so index 0 is a8. Black uses
+// the same tables with the rows flipped.
+#[rustfmt::skip]
+const PAWN_TABLE: [Score; 64] = table(
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         50,  50,  50,  50,  50,  50,  50,  50,
+         10,  10,  20,  30,  30,  20,  10,  10,
+          5,   5,  10,  25,  25,  10,   5,   5,
+          0,   0,   0,  20,  20,   0,   0,   0,
+          5,  -5, -10,   0,   0, -10,  -5,   5,
+          5,  10,  10, -20, -20,  10,  10,   5,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         80,  80,  80,  80,  80,  80,  80,  80,
+         50,  50,  50,  50,  50,  50,  50,  50,
+         30,  30,  30,  30,  30,  30,  30,  30,
+         20,  20,  20,  20,  20,  20,  20,  20,
+         10,  10,  10,  10,  10,  10,  10,  10,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+);
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [Score; 64] = table(
+    [
+        -50, -40, -30, -30, -30, -30, -40, -50,
+        -40, -20,   0,   0,   0,   0, -20, -40,
+        -30,   0,  10,  15,  15,  10,   0, -30,
+        -30,   5,  15,  20,  20,  15,   5, -30,
+        -30,   0,  15,  20,  20,  15,   0, -30,
+        -30,   5,  10,  15,  15,  10,   5, -30,
+        -40, -20,   0,   5,   5,   0, -20, -40,
+        -50, -40, -30, -30, -30, -30, -40, -50,
+    ],
+    [
+        -50, -40, -30, -30, -30, -30, -40, -50,
+        -40, -20,   0,   0,   0,   0, -20, -40,
+        -30,   0,  10,  15,  15,  10,   0, -30,
+        -30,   5,  15,  20,  20,  15,   5, -30,
+        -30,   0,  15,  20,  20,  15,   0, -30,
+        -30,   5,  10,  15,  15,  10,   5, -30,
+        -40, -20,   0,   5,   5,   0, -20, -40,
+        -50, -40, -30, -30, -30, -30, -40, -50,
+    ],
+);
+
+#[rustfmt::skip]
+const BISHOP_TABLE: [Score; 64] = table(
+    [
+        -20, -10, -10, -10, -10, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,  10,  10,   5,   0, -10,
+        -10,   5,   5,  10,  10,   5,   5, -10,
+        -10,   0,  10,  10,  10,  10,   0, -10,
+        -10,  10,  10,  10,  10,  10,  10, -10,
+        -10,   5,   0,   0,   0,   0,   5, -10,
+        -20, -10, -10, -10, -10, -10, -10, -20,
+    ],
+    [
+        -20, -10, -10, -10, -10, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,  10,  10,   5,   0, -10,
+        -10,   5,  10,  15,  15,  10,   5, -10,
+        -10,   5,  10,  15,  15,  10,   5, -10,
+        -10,   0,   5,  10,  10,   5,   0, -10,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -20, -10, -10, -10, -10, -10, -10, -20,
+    ],
+);
+
+#[rustfmt::skip]
+const ROOK_TABLE: [Score; 64] = table(
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+          5,  10,  10,  10,  10,  10,  10,   5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+          0,   0,   0,   5,   5,   0,   0,   0,
+    ],
+    [
+          5,   5,   5,   5,   5,   5,   5,   5,
+         10,  10,  10,  10,  10,  10,  10,  10,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+);
+
+#[rustfmt::skip]
+const QUEEN_TABLE: [Score; 64] = table(
+    [
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+         -5,   0,   5,   5,   5,   5,   0,  -5,
+          0,   0,   5,   5,   5,   5,   0,  -5,
+        -10,   5,   5,   5,   5,   5,   0, -10,
+        -10,   0,   5,   0,   0,   0,   0, -10,
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+    ],
+    [
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+         -5,   0,   5,  10,  10,   5,   0,  -5,
+         -5,   0,   5,  10,  10,   5,   0,  -5,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+    ],
+);
+
+// The king stays tucked away behind its pawns in the middlegame, and heads for the centre in the endgame
+#[rustfmt::skip]
+const KING_TABLE: [Score; 64] = table(
+    [
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -20, -30, -30, -40, -40, -30, -30, -20,
+        -10, -20, -20, -20, -20, -20, -20, -10,
+         20,  20,   0,   0,   0,   0,  20,  20,
+         20,  30,  10,   0,   0,  10,  30,  20,
+    ],
+    [
+        -50, -40, -30, -20, -20, -30, -40, -50,
+        -30, -20, -10,   0,   0, -10, -20, -30,
+        -30, -10,  20,  30,  30,  20, -10, -30,
+        -30, -10,  30,  40,  40,  30, -10, -30,
+        -30, -10,  30,  40,  40,  30, -10, -30,
+        -30, -10,  20,  30,  30,  20, -10, -30,
+        -30, -30,   0,   0,   0,   0, -30, -30,
+        -50, -30, -30, -30, -30, -30, -30, -50,
+    ],
+);
+
+// Indexed by Piece::index
+const TABLES: [&[Score; 64]; 6] = [&PAWN_TABLE, &KNIGHT_TABLE, &BISHOP_TABLE, &ROOK_TABLE, &QUEEN_TABLE, &KING_TABLE];
+
+const DOUBLED_PAWN: Score = Score::new(-10, -20);
+const ISOLATED_PAWN: Score = Score::new(-10, -15);
+// Indexed by how many rows the pawn has moved up from its starting row
+const PASSED_PAWN: [Score; 7] = [
+    Score::new(0, 0),
+    Score::new(5, 10),
+    Score::new(10, 20),
+    Score::new(15, 35),
+    Score::new(25, 60),
+    Score::new(40, 100),
+    Score::new(60, 150),
+];
+
+// Per square a piece attacks that is not taken by one of its own pieces, indexed by Piece::index
+const MOBILITY: [Score; 6] = [
+    Score::new(0, 0),
+    Score::new(4, 4),
+    Score::new(5, 5),
+    Score::new(2, 4),
+    Score::new(1, 2),
+    Score::new(0, 0),
+];
+
+// Own pawns just in front of the king, and enemy attacks on the squares around it. Only matters
+// in the middlegame, when there is enough material left for an attack to work.
+const PAWN_SHIELD: Score = Score::new(12, 0);
+const KING_ZONE_ATTACK: Score = Score::new(-8, 0);
+
+const fn table(mg: [i32; 64], eg: [i32; 64]) -> [Score; 64] {
+    let mut table = [Score::new(0, 0); 64];
+    let mut index = 0;
+    while index < 64 {
+        table[index] = Score::new(mg[index], eg[index]);
+        index += 1;
+    }
+    table
+}
+
+const FILE_A: Bitboard = 0x0101_0101_0101_0101;
+
+fn file_bb(column: i32) -> Bitboard {
+    FILE_A << column
+}
+
+fn adjacent_files(column: i32) -> Bitboard {
+    let mut files = 0;
+    if column > 0 {
+        files |= file_bb(column - 1);
+    }
+    if column < 7 {
+        files |= file_bb(column + 1);
+    }
+    files
+}
+
+// Every square on the rows in front of a pawn of the given colour on the given row
+fn rows_ahead(colour: Colour, row: i32) -> Bitboard {
+    match colour {
+        White if row <= 0 => 0,
+        White => (1u64 << (row * 8)) - 1,
+        Black if row >= 7 => 0,
+        Black => !((1u64 << ((row + 1) * 8)) - 1),
+    }
+}
+
+// Index of the same square from the other side of the board, so black can use white's tables
+fn relative_index(colour: Colour, index: i32) -> usize {
+    match colour {
+        White => index as usize,
+        Black => (index ^ 56) as usize,
+    }
+}
+
+// How good the position is for the given colour, in centipawns
+pub fn evaluate_for(board: &Board, colour: Colour) -> i32 {
+    let score = side_score(board, colour) - side_score(board, !colour);
+
+    let phase: i32 = [Knight, Bishop, Rook, Queen]
+        .iter()
+        .map(|piece| PHASE[piece.index()] * (board.piece_bb(White, *piece) | board.piece_bb(Black, *piece)).count_ones() as i32)
+        .sum::<i32>()
+        .min(MAX_PHASE);
+
+    (score.mg * phase + score.eg * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+// How good the position is for the side to move, which is what the search wants
+pub fn evaluate(board: &Board) -> i32 {
+    evaluate_for(board, board.turn)
+}
+
+// Everything in favour of one colour, ignoring the other colour's score
+fn side_score(board: &Board, colour: Colour) -> Score {
+    let mut score = Score::default();
+    let own = board.colour_bb(colour);
+    let occupied = board.occupied();
+
+    for piece in [Pawn, Knight, Bishop, Rook, Queen, King] {
+        for index in Indexes(board.piece_bb(colour, piece)) {
+            score += MATERIAL[piece.index()] + TABLES[piece.index()][relative_index(colour, index)];
+
+            let attacks = match piece {
+                Knight => KNIGHT_ATTACKS[index as usize],
+                Bishop => bishop_attacks(index, occupied),
+                Rook => rook_attacks(index, occupied),
+                Queen => queen_attacks(index, occupied),
+                _ => 0
+            };
+            score += MOBILITY[piece.index()] * (attacks & !own).count_ones() as i32;
+        }
+    }
+
+    score + pawn_structure(board, colour) + king_safety(board, colour)
+}
+
+fn pawn_structure(board: &Board, colour: Colour) -> Score {
+    let mut score = Score::default();
+    let pawns = board.piece_bb(colour, Pawn);
+    let enemy_pawns = board.piece_bb(!colour, Pawn);
+
+    for column in 0..8 {
+        let count = (pawns & file_bb(column)).count_ones() as i32;
+        if count > 1 {
+            score += DOUBLED_PAWN * (count - 1);
+        }
+        if count > 0 && pawns & adjacent_files(column) == 0 {
+            score += ISOLATED_PAWN * count;
+        }
+    }
+
+    // Passed pawns have no enemy pawns in front of them on their own file or the files beside it
+    for index in Indexes(pawns) {
+        let (row, column) = (index / 8, index % 8);
+        let front = rows_ahead(colour, row) & (file_bb(column) | adjacent_files(column));
+        if enemy_pawns & front == 0 {
+            let advanced = match colour {
+                White => 6 - row,
+                Black => row - 1,
+            };
+            score += PASSED_PAWN[advanced.clamp(0, 6) as usize];
+        }
+    }
+
+    score
+}
+
+fn king_safety(board: &Board, colour: Colour) -> Score {
+    let king = match Indexes(board.piece_bb(colour, King)).next() {
+        Some(king) => king,
+        None => return Score::default()
+    };
+    let zone = KING_ATTACKS[king as usize] | square_bb(king);
+
+    // Pawns on the three files around the king, on the two rows in front of it
+    let (row, column) = (king / 8, king % 8);
+    let near_rows = rows_ahead(colour, row) & !rows_ahead(colour, row + if colour == White { -2 } else { 2 });
+    let shield = board.piece_bb(colour, Pawn) & (file_bb(column) | adjacent_files(column)) & near_rows;
+    let mut score = PAWN_SHIELD * shield.count_ones().min(3) as i32;
+
+    let enemy = !colour;
+    let occupied = board.occupied();
+    let mut attacks = 0;
+    for index in Indexes(board.piece_bb(enemy, Knight)) {
+        attacks += (KNIGHT_ATTACKS[index as usize] & zone).count_ones();
+    }
+    for index in Indexes(board.piece_bb(enemy, Bishop)) {
+        attacks += (bishop_attacks(index, occupied) & zone).count_ones();
+    }
+    for index in Indexes(board.piece_bb(enemy, Rook)) {
+        attacks += (rook_attacks(index, occupied) & zone).count_ones();
+    }
+    for index in Indexes(board.piece_bb(enemy, Queen)) {
+        attacks += (queen_attacks(index, occupied) & zone).count_ones() * 2;
+    }
+    score += KING_ZONE_ATTACK * attacks as i32;
+
+    score
+}
+
+// Rough value of a piece, for deciding which captures to look at first
+pub(super) fn piece_value(piece: Piece) -> i32 {
+    match piece {
+        King => 20000,
+        _ => MATERIAL[piece.index()].mg,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Black, Board, White, START_FEN};
+    use super::{evaluate, evaluate_for};
+
+    // The same position with the colours swapped and the board turned around
+    fn mirror(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let placement: Vec<String> = fields[0]
+            .split('/')
+            .rev()
+            .map(|rank| rank.chars().map(|v| if v.is_ascii_uppercase() { v.to_ascii_lowercase() } else { v.to_ascii_uppercase() }).collect())
+            .collect();
+        let turn = if fields[1] == "w" { "b" } else { "w" };
+        format!("{} {} - - 0 1", placement.join("/"), turn)
+    }
+
+    fn eval(fen: &str) -> i32 {
+        evaluate_for(&Board::from_fen(fen).unwrap(), White)
+    }
+
+    #[test]
+    fn symmetry() {
+        assert_eq!(evaluate(&Board::new()), 0);
+
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            START_FEN,
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let mirrored = Board::from_fen(&mirror(fen)).unwrap();
+            assert_eq!(evaluate(&board), evaluate(&mirrored), "{}", fen);
+            assert_eq!(evaluate_for(&board, White), -evaluate_for(&board, Black));
+        }
+    }
+
+    #[test]
+    fn material() {
+        assert!(eval("4k3/8/8/8/8/8/8/3QK3 w - - 0 1") > 800);
+        assert!(eval("4k3/8/8/8/8/8/8/3RK3 w - - 0 1") > eval("4k3/8/8/8/8/8/8/3NK3 w - - 0 1"));
+        assert!(eval("3qk3/8/8/8/8/8/8/3RK3 w - - 0 1") < 0);
+    }
+
+    #[test]
+    fn positional() {
+        // Knight in the centre rather than on the rim
+        assert!(eval("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1") > eval("4k3/8/8/8/N7/8/8/4K3 w - - 0 1"));
+        // Doubled and isolated pawns
+        assert!(eval("4k3/pp6/8/8/8/8/PP6/4K3 w - - 0 1") > eval("4k3/pp6/8/8/8/P7/P7/4K3 w - - 0 1"));
+        assert!(eval("4k3/ppp5/8/8/8/8/PPP5/4K3 w - - 0 1") > eval("4k3/ppp5/8/8/8/8/PP1P4/4K3 w - - 0 1"));
+        // The further a passed pawn has gone the better
+        assert!(eval("4k3/8/1P6/8/8/8/8/4K3 w - - 0 1") > eval("4k3/8/8/8/8/1P6/8/4K3 w - - 0 1"));
+        // Same material, but only in the first can the b pawn not be stopped by another pawn
+        assert!(eval("4k3/7p/8/1P6/8/8/8/4K3 w - - 0 1") > eval("4k3/p7/8/1P6/8/8/8/4K3 w - - 0 1") + 20);
+    }
+
+    #[test]
+    fn king_safety() {
+        // Castled behind its pawns rather than out in front of them in the middlegame
+        let castled = eval("r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2N2N2/PPPP1PPP/R1BQ1RK1 w - - 0 1");
+        let exposed = eval("r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2N2NK1/PPPP1PPP/R1BQ1R2 w - - 0 1");
+        assert!(castled > exposed + 30);
+
+        // But in the endgame the king should come to the centre
+        assert!(eval("4k3/8/8/8/3K4/8/8/8 w - - 0 1") > eval("4k3/8/8/8/8/8/8/K7 w - - 0 1"));
+    }
+}
@@ -0,0 +1,302 @@
+use crate::*;
+use super::{Limits, Player, Score, Sprt, SprtVerdict};
+
+// Short openings that games start from when no others are given, so that engines which always
+// play the same way do not play the same game over and over
+pub const DEFAULT_OPENINGS: [&str; 12] = [
+    "e4 e5 Nf3 Nc6 Bb5",
+    "e4 e5 Nf3 Nc6 Bc4",
+    "e4 c5 Nf3 d6",
+    "e4 c5 Nc3 Nc6",
+    "e4 e6 d4 d5",
+    "e4 c6 d4 d5",
+    "d4 d5 c4 e6",
+    "d4 d5 c4 c6",
+    "d4 Nf6 c4 g6",
+    "d4 Nf6 c4 e6 Nc3 Bb4",
+    "c4 e5",
+    "Nf3 d5 g3",
+];
+
+// When to end a game early because the result is already clear. Scores are those the engines
+// give for their own moves, in centipawns.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Adjudication {
+    // Drawn once both engines have scored the game within draw_score of level for draw_moves moves
+    // each, as long as at least draw_after half moves have been played
+    pub draw_score: i32,
+    pub draw_moves: usize,
+    pub draw_after: usize,
+    // Won once both engines agree that one side is at least win_score ahead for win_moves moves each
+    pub win_score: i32,
+    pub win_moves: usize,
+    // Games still going after this many half moves are drawn
+    pub max_ply: usize,
+}
+
+impl Default for Adjudication {
+    fn default() -> Self {
+        Adjudication {
+            draw_score: 10,
+            draw_moves: 8,
+            draw_after: 80,
+            win_score: 1000,
+            win_moves: 4,
+            max_ply: 400,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchSettings {
+    pub games: usize,
+    // For each move
+    pub limits: Limits,
+    // Each is played twice in a row, once with each engine as white
+    pub openings: Vec<Game>,
+    pub adjudication: Option<Adjudication>,
+    // Ends the match early once the test has an answer
+    pub sprt: Option<Sprt>,
+    // Event tag of the games
+    pub event: String,
+}
+
+impl Default for MatchSettings {
+    fn default() -> Self {
+        MatchSettings {
+            games: 100,
+            limits: Limits::time(std::time::Duration::from_millis(100)),
+            openings: openings(&DEFAULT_OPENINGS.join("\n")).unwrap(),
+            adjudication: Some(Adjudication::default()),
+            sprt: None,
+            event: String::from("Engine match"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchResult {
+    // From the first engine's point of view
+    pub score: Score,
+    pub games: Vec<PgnGame>,
+    // Only if the settings had a test
+    pub sprt: Option<SprtVerdict>,
+}
+
+// Reads openings as PGN games, whose main lines are played, or as one per line, either a FEN or
+// SAN moves from the starting position
+pub fn openings(text: &str) -> Result<Vec<Game>, String> {
+    if text.trim_start().starts_with('[') {
+        return PgnGame::parse_all(text)?
+            .iter()
+            .map(|pgn| {
+                let mut game = Game::from_board(pgn.start_board()?);
+                for _move in pgn.main_line()? {
+                    game.play(_move).map_err(|err| err.to_string())?;
+                }
+                Ok(game)
+            })
+            .collect();
+    }
+
+    text.lines()
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty() && !v.starts_with('#'))
+        .map(|line| {
+            if let Ok(board) = Board::from_fen(line) {
+                return Ok(Game::from_board(board));
+            }
+            let mut game = Game::new();
+            for san in line.split_whitespace() {
+                game.play_san(san).map_err(|err| format!("Opening {}: {}: {}", line, san, err))?;
+            }
+            Ok(game)
+        })
+        .collect()
+}
+
+// Plays games between two engines, swapping colours after each one, and calls on_game with each
+// game as it finishes along with the score so far
+pub fn play_match(first: &mut dyn Player, second: &mut dyn Player, settings: &MatchSettings, mut on_game: impl FnMut(&PgnGame, Score)) -> Result<MatchResult, String> {
+    if settings.openings.is_empty() {
+        return Err("No openings to play".to_string());
+    }
+
+    let mut result = MatchResult { score: Score::default(), games: Vec::new(), sprt: settings.sprt.map(|_| SprtVerdict::Continue) };
+    for index in 0..settings.games {
+        let opening = &settings.openings[(index / 2) % settings.openings.len()];
+        let first_white = index % 2 == 0;
+        let mut pgn = if first_white {
+            play_game(first, second, opening, settings)?
+        } else {
+            play_game(second, first, opening, settings)?
+        };
+        pgn.set_tag("Event", &settings.event);
+        pgn.set_tag("Round", &(index + 1).to_string());
+
+        match (pgn.result.as_str(), first_white) {
+            ("1-0", true) | ("0-1", false) => result.score.wins += 1,
+            ("0-1", true) | ("1-0", false) => result.score.losses += 1,
+            _ => result.score.draws += 1,
+        }
+        on_game(&pgn, result.score);
+        result.games.push(pgn);
+
+        if let Some(sprt) = settings.sprt {
+            let verdict = sprt.verdict(result.score);
+            result.sprt = Some(verdict);
+            if verdict != SprtVerdict::Continue {
+                break;
+            }
+        }
+    }
+    Ok(result)
+}
+
+// An engine that fails to give a legal move loses the game
+fn play_game(white: &mut dyn Player, black: &mut dyn Player, opening: &Game, settings: &MatchSettings) -> Result<PgnGame, String> {
+    white.new_game()?;
+    black.new_game()?;
+
+    let mut game = opening.clone();
+    // What each engine thought of the position after its move, from white's point of view
+    let mut scores = Vec::new();
+    let (result, termination, reason) = loop {
+        match game.state() {
+            GameState::Playing => {}
+            GameState::Checkmate(White) => break ("0-1", "normal", String::from("Black mates")),
+            GameState::Checkmate(Black) => break ("1-0", "normal", String::from("White mates")),
+            GameState::Stalemate => break ("1/2-1/2", "normal", String::from("Stalemate")),
+            GameState::Draw(reason) => break ("1/2-1/2", "normal", format!("Draw by {}", reason)),
+        }
+        if let Some(rules) = settings.adjudication {
+            if let Some(result) = adjudicate(&scores, game.ply(), &rules) {
+                let reason = match result {
+                    "1-0" => "White wins by adjudication",
+                    "0-1" => "Black wins by adjudication",
+                    _ => "Draw by adjudication",
+                };
+                break (result, "adjudication", reason.to_string());
+            }
+        }
+
+        let turn = game.board().turn;
+        let (forfeit, search) = match turn {
+            White => ("0-1", white.choose_move(&game, settings.limits)),
+            Black => ("1-0", black.choose_move(&game, settings.limits)),
+        };
+        let search = match search {
+            Ok(search) => search,
+            Err(err) => break (forfeit, "rules infraction", format!("{:?} forfeits: {}", turn, err)),
+        };
+        let _move = match search.best_move {
+            Some(_move) => _move,
+            None => break (forfeit, "rules infraction", format!("{:?} did not move", turn)),
+        };
+        if let Err(err) = game.play(_move) {
+            break (forfeit, "rules infraction", format!("{:?} played {}: {}", turn, _move, err));
+        }
+        scores.push(if turn == White { search.score } else { -search.score });
+    };
+
+    let mut pgn = PgnGame::from_moves(game.start(), &game.played())?;
+    pgn.set_tag("White", &white.name());
+    pgn.set_tag("Black", &black.name());
+    pgn.result = result.to_string();
+    pgn.set_tag("Result", result);
+    pgn.set_tag("Termination", termination);
+    if let Some(last) = pgn.moves.last_mut() {
+        last.comment = Some(reason);
+    }
+    Ok(pgn)
+}
+
+// The result, if the scores of the last few moves make it clear
+fn adjudicate(scores: &[i32], ply: usize, rules: &Adjudication) -> Option<&'static str> {
+    let recent = |moves: usize| scores.get(scores.len().checked_sub(moves * 2)?..).filter(|_| moves > 0);
+
+    if let Some(recent) = recent(rules.win_moves) {
+        if recent.iter().all(|v| *v >= rules.win_score) {
+            return Some("1-0");
+        }
+        if recent.iter().all(|v| *v <= -rules.win_score) {
+            return Some("0-1");
+        }
+    }
+    if ply >= rules.draw_after && recent(rules.draw_moves).is_some_and(|v| v.iter().all(|v| v.abs() <= rules.draw_score)) {
+        return Some("1/2-1/2");
+    }
+    if ply >= rules.max_ply {
+        return Some("1/2-1/2");
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Board, Game, PgnGame};
+    use crate::engine::{Engine, Limits, SearchOptions, Sprt};
+    use super::{adjudicate, openings, play_match, Adjudication, MatchSettings};
+
+    #[test]
+    fn reads_openings() {
+        let games = openings("# Comment\ne4 e5 Nf3\n\n4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\n").unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].ply(), 3);
+        assert_eq!(games[1].board(), &Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap());
+
+        let games = openings("[Event \"?\"]\n\n1. d4 (1. e4) d5 *\n\n[Event \"?\"]\n\n1. c4 *\n").unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].start().to_san_line(&games[0].played()), vec!["d4", "d5"]);
+
+        assert!(openings("e4 e4").is_err());
+    }
+
+    #[test]
+    fn adjudication() {
+        let rules = Adjudication { draw_score: 10, draw_moves: 2, draw_after: 20, win_score: 500, win_moves: 2, max_ply: 100 };
+        assert_eq!(adjudicate(&[600, 700, 800], 40, &rules), None);
+        assert_eq!(adjudicate(&[600, 700, 800, 900], 40, &rules), Some("1-0"));
+        assert_eq!(adjudicate(&[-600, -700, -800, -900], 40, &rules), Some("0-1"));
+        // Only once both engines agree
+        assert_eq!(adjudicate(&[-600, -700, 800, -900], 40, &rules), None);
+
+        assert_eq!(adjudicate(&[0, 5, -5, 10], 40, &rules), Some("1/2-1/2"));
+        assert_eq!(adjudicate(&[0, 5, -5, 10], 10, &rules), None);
+        assert_eq!(adjudicate(&[], 100, &rules), Some("1/2-1/2"));
+    }
+
+    #[test]
+    fn plays_a_match() {
+        let mut first = Engine::new();
+        let mut second = Engine::new();
+        second.set_options(SearchOptions::none());
+        let settings = MatchSettings {
+            games: 4,
+            limits: Limits::depth(2),
+            openings: openings("e4 e5\nd4 d5").unwrap(),
+            adjudication: Some(Adjudication { max_ply: 40, ..Adjudication::default() }),
+            sprt: Some(Sprt::new(0.0, 10.0)),
+            ..MatchSettings::default()
+        };
+
+        let mut played = 0;
+        let result = play_match(&mut first, &mut second, &settings, |_, score| {
+            played += 1;
+            assert_eq!(score.games(), played);
+        }).unwrap();
+        assert_eq!(result.games.len(), 4);
+        assert_eq!(result.score.games(), 4);
+        assert!(result.sprt.is_some());
+
+        // Colours swap each game, and each opening is played twice
+        for (index, pgn) in result.games.iter().enumerate() {
+            assert_eq!(pgn.tag("Round"), Some((index + 1).to_string().as_str()));
+            assert_ne!(pgn.result, "*");
+            assert!(pgn.tag("Termination").is_some());
+            let game = Game::from_pgn(&PgnGame::parse(&pgn.to_string()).unwrap()).unwrap();
+            let first = if index < 2 { "e4" } else { "d4" };
+            assert_eq!(game.start().to_san_line(&game.played())[0], first);
+        }
+    }
+}
@@ -0,0 +1,534 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use crate::*;
+
+mod eval;
+mod match_runner;
+mod movepick;
+mod player;
+mod search;
+mod see;
+mod stats;
+mod tt;
+mod uci;
+
+pub use eval::{evaluate, evaluate_for};
+pub use match_runner::{openings, play_match, Adjudication, MatchResult, MatchSettings, DEFAULT_OPENINGS};
+pub use movepick::{Heuristics, MovePicker};
+pub use player::Player;
+pub use stats::{Score, Sprt, SprtVerdict};
+pub use tt::{Bound, Entry, TranspositionTable, DEFAULT_HASH_SIZE};
+pub use uci::UciEngine;
+
+// Scores are in centipawns from the point of view of the side to move. A mate is scored as MATE
+// minus the number of half moves until it happens, so that quicker mates score higher.
+pub const MATE: i32 = 32000;
+pub const INFINITY: i32 = MATE + 1;
+// Deepest the search will ever go, which also caps searches without a depth limit
+pub const MAX_DEPTH: u32 = 64;
+
+// Scores this close to MATE are mates rather than material
+const MATE_BOUND: i32 = MATE - MAX_DEPTH as i32 * 2;
+
+// Time kept back from each move when playing on a clock
+const CLOCK_OVERHEAD: Duration = Duration::from_millis(50);
+
+// Half width of the first aspiration window, and how wide it can get before it is dropped
+const ASPIRATION_WINDOW: i32 = 25;
+const MAX_ASPIRATION_WINDOW: i32 = 400;
+
+// Number of moves until mate, positive when the side to move is giving mate and negative when it is
+// being mated. None if the score is not a mate.
+pub fn mate_in(score: i32) -> Option<i32> {
+    if score > MATE_BOUND {
+        Some((MATE - score + 1) / 2)
+    } else if score < -MATE_BOUND {
+        Some(-(MATE + score) / 2)
+    } else {
+        None
+    }
+}
+
+// Short description of a score for people to read, e.g. +1.25 or mate in 3
+pub fn describe_score(score: i32) -> String {
+    match mate_in(score) {
+        Some(moves) if moves > 0 => format!("mate in {}", moves),
+        Some(moves) => format!("mated in {}", -moves),
+        None => format!("{:+.2}", score as f64 / 100.0),
+    }
+}
+
+// When to stop searching. The search stops at whichever limit is reached first, and with no limits
+// it carries on to MAX_DEPTH.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub depth: Option<u32>,
+    pub time: Option<Duration>,
+    pub nodes: Option<u64>,
+}
+
+impl Limits {
+    pub fn depth(depth: u32) -> Limits {
+        Limits { depth: Some(depth), ..Limits::default() }
+    }
+
+    pub fn time(time: Duration) -> Limits {
+        Limits { time: Some(time), ..Limits::default() }
+    }
+
+    pub fn nodes(nodes: u64) -> Limits {
+        Limits { nodes: Some(nodes), ..Limits::default() }
+    }
+
+    // Time for one move, for a side with time_left on its clock that gains increment after each move.
+    // Without a number of moves until the next time control, the game is expected to last another
+    // 30 moves or so. A little time is always kept back for talking to whatever is running the engine.
+    pub fn clock(time_left: Duration, increment: Duration, moves_to_go: Option<u32>) -> Limits {
+        let moves = moves_to_go.unwrap_or(30).max(1);
+        let time = time_left / moves + increment * 3 / 4;
+        let most = time_left.saturating_sub(CLOCK_OVERHEAD.min(time_left / 2));
+        Limits::time(time.min(most).max(Duration::from_millis(1)))
+    }
+}
+
+// Parts of the search that can be switched off, to measure what each of them is worth. All of them
+// are on by default, and with none of them the search is plain alpha-beta.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub null_move: bool,
+    pub late_move_reductions: bool,
+    pub futility: bool,
+    pub reverse_futility: bool,
+    pub aspiration_windows: bool,
+    pub check_extensions: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            null_move: true,
+            late_move_reductions: true,
+            futility: true,
+            reverse_futility: true,
+            aspiration_windows: true,
+            check_extensions: true,
+        }
+    }
+}
+
+impl SearchOptions {
+    pub fn none() -> SearchOptions {
+        SearchOptions {
+            null_move: false,
+            late_move_reductions: false,
+            futility: false,
+            reverse_futility: false,
+            aspiration_windows: false,
+            check_extensions: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    // None only if there are no legal moves
+    pub best_move: Option<Move>,
+    pub score: i32,
+    // Deepest iteration that was finished
+    pub depth: u32,
+    pub nodes: u64,
+    pub time: Duration,
+    // The line of moves both sides are expected to play, starting with best_move
+    pub pv: Vec<Move>,
+}
+
+pub struct Engine {
+    // What it is called when playing as a Player, e.g. in the games of a match
+    name: String,
+    // Kept between searches, and only emptied by new_game
+    tt: TranspositionTable,
+    // Move ordering tables, which are also kept between searches
+    heuristics: Heuristics,
+    options: SearchOptions,
+    limits: Limits,
+    start: Instant,
+    nodes: u64,
+    // Moves played from the root to the position being searched
+    stack: Vec<Option<Move>>,
+    // Set once a limit is reached, after which the search unwinds and the unfinished iteration is thrown away
+    stopped: bool,
+    // Lets another thread stop the search, with the same effect as reaching a limit
+    stop: Arc<AtomicBool>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine::with_hash_size(DEFAULT_HASH_SIZE)
+    }
+
+    // Size of the transposition table in megabytes
+    pub fn with_hash_size(megabytes: usize) -> Engine {
+        Engine {
+            name: String::from("rust-chess"),
+            tt: TranspositionTable::new(megabytes),
+            heuristics: Heuristics::new(),
+            options: SearchOptions::default(),
+            limits: Limits::default(),
+            start: Instant::now(),
+            nodes: 0,
+            stack: Vec::new(),
+            stopped: false,
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // So that engines set up differently can be told apart
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    pub fn set_hash_size(&mut self, megabytes: usize) {
+        self.tt.resize(megabytes);
+    }
+
+    // Forgets everything from earlier searches, which no longer helps once a different game starts
+    pub fn new_game(&mut self) {
+        self.tt.clear();
+        self.heuristics.clear();
+    }
+
+    pub fn options(&self) -> SearchOptions {
+        self.options
+    }
+
+    pub fn set_options(&mut self, options: SearchOptions) {
+        self.options = options;
+    }
+
+    // Setting the flag stops the search that is running, which still returns the result of the
+    // deepest iteration it finished. It is cleared when a search starts.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    pub fn tt(&self) -> &TranspositionTable {
+        &self.tt
+    }
+
+    // Searches one depth deeper each time until a limit is reached, returning the result of the
+    // deepest search that was finished
+    pub fn search(&mut self, board: &Board, limits: Limits) -> SearchResult {
+        self.search_with(board, limits, |_| {})
+    }
+
+    // The same as search, but calls report with the result so far after each iteration, e.g. for
+    // showing how the search is going
+    pub fn search_with(&mut self, board: &Board, limits: Limits, mut report: impl FnMut(&SearchResult)) -> SearchResult {
+        self.stop.store(false, Ordering::Relaxed);
+        self.limits = limits;
+        self.start = Instant::now();
+        self.nodes = 0;
+        self.stack.clear();
+        self.stopped = false;
+        self.tt.new_search();
+        self.heuristics.new_search();
+
+        let mut board = board.clone();
+        let mut result = SearchResult {
+            best_move: None,
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            time: Duration::ZERO,
+            pv: Vec::new(),
+        };
+
+        let moves = board.legal_moves();
+        if moves.is_empty() {
+            result.score = if board.in_check() { -MATE } else { 0 };
+            return result;
+        }
+        // Always have a move to play, even if the first iteration does not finish
+        result.best_move = Some(moves[0]);
+
+        let max_depth = limits.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH);
+        for depth in 1..=max_depth {
+            let mut pv = Vec::new();
+            let score = self.aspiration_search(&mut board, &moves, depth, result.score, result.pv.first().copied(), &mut pv);
+
+            // A search cut short may not have looked at the best move at all, unless it was the
+            // best move of the last iteration, which is searched first
+            if self.stopped {
+                if depth == 1 && !pv.is_empty() {
+                    result.best_move = pv.first().copied();
+                    result.pv = pv;
+                    result.score = score;
+                }
+                break;
+            }
+
+            result.best_move = pv.first().copied();
+            result.pv = pv;
+            result.score = score;
+            result.depth = depth;
+            result.nodes = self.nodes;
+            result.time = self.start.elapsed();
+            report(&result);
+
+            // No need to look any further once a forced mate has been found
+            if mate_in(score).is_some_and(|v| v > 0 && v * 2 - 1 <= depth as i32) {
+                break;
+            }
+        }
+
+        result.nodes = self.nodes;
+        result.time = self.start.elapsed();
+        result
+    }
+
+    // Searches with a narrow window around the score of the last iteration, which lets more of the
+    // tree be cut off. If the score turns out to be outside it, the window is widened on that side
+    // and the search is done again.
+    fn aspiration_search(&mut self, board: &mut Board, moves: &[Move], depth: u32, last_score: i32, best: Option<Move>, pv: &mut Vec<Move>) -> i32 {
+        if !self.options.aspiration_windows || depth < 4 || last_score.abs() >= MATE_BOUND {
+            return self.search_root(board, moves, depth, (-INFINITY, INFINITY), best, pv);
+        }
+
+        let mut delta = ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = (last_score - delta, last_score + delta);
+        loop {
+            pv.clear();
+            let score = self.search_root(board, moves, depth, (alpha, beta), best, pv);
+            if self.stopped {
+                return score;
+            }
+
+            // Past a point it is quicker to give up on the window altogether
+            delta *= 2;
+            let open = delta > MAX_ASPIRATION_WINDOW;
+            if score <= alpha {
+                alpha = if open { -INFINITY } else { last_score - delta };
+            } else if score >= beta {
+                beta = if open { INFINITY } else { last_score + delta };
+            } else {
+                return score;
+            }
+        }
+    }
+
+    // Checks the node and time limits. The clock is only looked at every so often, as it is slow to read.
+    fn should_stop(&mut self) -> bool {
+        if self.stopped {
+            return true;
+        }
+        if self.stop.load(Ordering::Relaxed) {
+            self.stopped = true;
+        }
+        if self.limits.nodes.is_some_and(|v| self.nodes >= v) {
+            self.stopped = true;
+        }
+        if self.nodes.is_multiple_of(1024) && self.limits.time.is_some_and(|v| self.start.elapsed() >= v) {
+            self.stopped = true;
+        }
+        self.stopped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use std::time::{Duration, Instant};
+    use crate::{Board, START_FEN};
+    use super::{describe_score, mate_in, Engine, Limits, SearchOptions, MATE};
+
+    fn best_move(fen: &str, depth: u32) -> String {
+        let board = Board::from_fen(fen).unwrap();
+        let result = Engine::new().search(&board, Limits::depth(depth));
+        board.to_san(result.best_move.unwrap())
+    }
+
+    #[test]
+    fn finds_mates() {
+        // Back rank mate
+        assert_eq!(best_move("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 2), "Ra8#");
+        // Scholar's mate
+        assert_eq!(best_move("r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5Q2/PPPP1PPP/RNB1K1NR w KQkq - 0 1", 3), "Qxf7#");
+
+        // Mate in two: Rd8+ Rxd8 Rxd8#
+        let board = Board::from_fen("2r4k/6pp/8/8/8/8/3R1PPP/3R2K1 w - - 0 1").unwrap();
+        let result = Engine::new().search(&board, Limits::depth(4));
+        assert_eq!(mate_in(result.score), Some(2));
+        assert_eq!(describe_score(result.score), "mate in 2");
+        assert_eq!(describe_score(-MATE + 4), "mated in 2");
+        assert_eq!(describe_score(-125), "-1.25");
+        assert_eq!(result.pv.len(), 3);
+    }
+
+    #[test]
+    fn wins_material() {
+        // Free queen
+        assert_eq!(best_move("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", 1), "Rxd5");
+        // Knight fork of king and queen
+        assert_eq!(best_move("q3k3/8/8/3N4/8/8/8/4K3 w - - 0 1", 3), "Nc7+");
+    }
+
+    #[test]
+    fn game_over() {
+        let mated = Board::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        let result = Engine::new().search(&mated, Limits::depth(3));
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, -MATE);
+        assert_eq!(mate_in(result.score), Some(0));
+
+        let stalemate = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(Engine::new().search(&stalemate, Limits::depth(3)).score, 0);
+    }
+
+    #[test]
+    fn limits() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        let mut engine = Engine::new();
+
+        let result = engine.search(&board, Limits::depth(3));
+        assert_eq!(result.depth, 3);
+        assert_eq!(result.pv.first().copied(), result.best_move);
+        assert!(board.legal_moves().contains(&result.best_move.unwrap()));
+
+        let result = engine.search(&board, Limits::nodes(2000));
+        assert!(result.nodes <= 2000);
+        assert!(result.best_move.is_some());
+
+        let start = Instant::now();
+        let result = engine.search(&board, Limits::time(Duration::from_millis(100)));
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert!(result.depth >= 1);
+    }
+
+    #[test]
+    fn horizon() {
+        // At depth 1 the queen takes the pawn, unless the search looks at the rook taking back
+        assert_ne!(best_move("4k3/8/3r4/8/3p4/8/8/3QK3 w - - 0 1", 1), "Qxd4");
+        // Winning a queen for a rook is fine though
+        assert_eq!(best_move("4k3/8/3r4/8/3q4/8/8/3RK3 w - - 0 1", 1), "Rxd4");
+    }
+
+    #[test]
+    fn transposition_table() {
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        // Pruning is switched off, as it would make the difference between the two searches smaller
+        let mut engine = Engine::new();
+        engine.set_options(SearchOptions::none());
+        let first = engine.search(&board, Limits::depth(4));
+        assert!(!engine.tt().is_empty());
+
+        // The second search can use everything the first one stored
+        let second = engine.search(&board, Limits::depth(4));
+        assert!(second.nodes < first.nodes / 2);
+        assert_eq!(second.score, first.score);
+
+        engine.new_game();
+        assert!(engine.tt().is_empty());
+        assert_eq!(engine.search(&board, Limits::depth(4)).nodes, first.nodes);
+
+        // Works the same with no room for more than one position
+        let mut engine = Engine::with_hash_size(0);
+        engine.set_options(SearchOptions::none());
+        assert_eq!(engine.search(&board, Limits::depth(4)).score, first.score);
+    }
+
+    #[test]
+    fn principal_variation() {
+        let mut board = Board::new();
+        let result = Engine::new().search(&board, Limits::depth(4));
+        assert_eq!(result.pv.len(), 4);
+        for _move in result.pv {
+            assert!(board.legal_moves().contains(&_move));
+            board.make_move(_move);
+        }
+    }
+
+    #[test]
+    fn pruning() {
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let mut engine = Engine::new();
+        engine.set_options(SearchOptions::none());
+        let plain = engine.search(&board, Limits::depth(5));
+
+        let mut engine = Engine::new();
+        let pruned = engine.search(&board, Limits::depth(5));
+        assert!(pruned.nodes < plain.nodes / 2);
+
+        // Each part can be switched off on its own, and the search still finds the mate
+        let mate = Board::from_fen("2r4k/6pp/8/8/8/8/3R1PPP/3R2K1 w - - 0 1").unwrap();
+        let switches: [fn(&mut SearchOptions); 6] = [
+            |v| v.null_move = false,
+            |v| v.late_move_reductions = false,
+            |v| v.futility = false,
+            |v| v.reverse_futility = false,
+            |v| v.aspiration_windows = false,
+            |v| v.check_extensions = false,
+        ];
+        for switch in switches {
+            let mut options = SearchOptions::default();
+            switch(&mut options);
+            let mut engine = Engine::new();
+            engine.set_options(options);
+            assert_eq!(engine.options(), options);
+            assert_eq!(mate_in(engine.search(&mate, Limits::depth(4)).score), Some(2));
+        }
+    }
+
+    #[test]
+    fn null_move() {
+        let mut board = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let before = board.clone();
+        let undo = board.make_null_move();
+        assert_eq!(board.turn, crate::Black);
+        assert_eq!(board.en_passant(), None);
+        // The same as the position with the other side to move, and the en passant capture gone
+        assert_eq!(board.key(), Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 b - - 0 1").unwrap().key());
+        board.unmake_null_move(undo);
+        assert_eq!(board, before);
+        assert_eq!(board.key(), before.key());
+    }
+
+    #[test]
+    fn stopping() {
+        let board = Board::new();
+        let mut engine = Engine::new();
+        let stop = engine.stop_flag();
+
+        // Each finished iteration is reported, and setting the flag ends the search after the next one
+        let mut depths = Vec::new();
+        let result = engine.search_with(&board, Limits::default(), |info| {
+            depths.push(info.depth);
+            if info.depth == 3 {
+                stop.store(true, Ordering::Relaxed);
+            }
+        });
+        assert_eq!(depths, vec![1, 2, 3]);
+        assert_eq!(result.depth, 3);
+        assert!(result.best_move.is_some());
+
+        // The flag does not carry over to the next search
+        assert_eq!(engine.search(&board, Limits::depth(2)).depth, 2);
+    }
+
+    #[test]
+    fn clock() {
+        let time = |v: Limits| v.time.unwrap().as_millis();
+        assert_eq!(time(Limits::clock(Duration::from_secs(60), Duration::ZERO, None)), 2000);
+        assert_eq!(time(Limits::clock(Duration::from_secs(60), Duration::from_secs(2), None)), 3500);
+        assert_eq!(time(Limits::clock(Duration::from_secs(60), Duration::ZERO, Some(10))), 6000);
+        // Never more than is left on the clock
+        assert_eq!(time(Limits::clock(Duration::from_millis(500), Duration::from_secs(2), Some(1))), 450);
+        assert_eq!(time(Limits::clock(Duration::ZERO, Duration::ZERO, None)), 1);
+    }
+}
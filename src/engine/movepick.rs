@@ -0,0 +1,331 @@
+use crate::*;
+use super::{eval, MAX_DEPTH};
+
+// History scores are kept within this, so that old cut offs fade out as new ones come in
+const MAX_HISTORY: i32 = 16384;
+
+// What the search has learned about which quiet moves tend to cause cut offs, used to try the
+// most promising ones first
+pub struct Heuristics {
+    // Two quiet moves per ply that caused a cut off in a sibling position
+    killers: Vec<[Option<Move>; 2]>,
+    // Indexed by colour, start index and end index
+    history: Vec<[[i32; 64]; 64]>,
+    // Quiet move that refuted a move last time, indexed by the colour, piece and end index of that move
+    counters: Vec<[[Option<Move>; 64]; 6]>,
+}
+
+impl Default for Heuristics {
+    fn default() -> Self {
+        Heuristics::new()
+    }
+}
+
+impl Heuristics {
+    pub fn new() -> Heuristics {
+        Heuristics {
+            killers: vec![[None; 2]; MAX_DEPTH as usize + 1],
+            history: vec![[[0; 64]; 64]; 2],
+            counters: vec![[[None; 64]; 6]; 2],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Heuristics::new();
+    }
+
+    // Killers only make sense for the positions of one search, but history is still a fair guide
+    // in the next one, so it is only made less important
+    pub fn new_search(&mut self) {
+        self.killers.fill([None; 2]);
+        for table in self.history.iter_mut() {
+            for row in table.iter_mut() {
+                for score in row.iter_mut() {
+                    *score /= 2;
+                }
+            }
+        }
+    }
+
+    pub fn killers(&self, ply: u32) -> [Option<Move>; 2] {
+        self.killers[(ply as usize).min(MAX_DEPTH as usize)]
+    }
+
+    pub fn history(&self, _move: Move) -> i32 {
+        self.history[_move.piece.colour.index()][_move.start.index as usize][_move.end.index as usize]
+    }
+
+    // The counter move to the opponent's last move
+    pub fn counter(&self, previous: Option<Move>) -> Option<Move> {
+        previous.and_then(|v| self.counters[v.piece.colour.index()][v.piece.variant.index()][v.end.index as usize])
+    }
+
+    // Records a quiet move that caused a beta cut off. Quiet moves that were tried before it and did
+    // not are made less likely to be tried early next time.
+    pub fn update(&mut self, _move: Move, previous: Option<Move>, tried: &[Move], depth: u32, ply: u32) {
+        let killers = &mut self.killers[(ply as usize).min(MAX_DEPTH as usize)];
+        if killers[0] != Some(_move) {
+            killers[1] = killers[0];
+            killers[0] = Some(_move);
+        }
+
+        if let Some(previous) = previous {
+            self.counters[previous.piece.colour.index()][previous.piece.variant.index()][previous.end.index as usize] = Some(_move);
+        }
+
+        let bonus = (depth * depth) as i32;
+        self.add_history(_move, bonus);
+        for other in tried.iter().filter(|v| **v != _move) {
+            self.add_history(*other, -bonus);
+        }
+    }
+
+    // Moves the score towards the limit by the bonus, by less the closer it already is
+    fn add_history(&mut self, _move: Move, bonus: i32) {
+        let bonus = bonus.clamp(-MAX_HISTORY, MAX_HISTORY);
+        let score = &mut self.history[_move.piece.colour.index()][_move.start.index as usize][_move.end.index as usize];
+        *score += bonus - *score * bonus.abs() / MAX_HISTORY;
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Stage {
+    HashMove,
+    Generate,
+    GoodCaptures,
+    Killers,
+    CounterMove,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+// Hands out the pseudo-legal moves of a position in the order most likely to cause an early cut
+// off: the best move stored for the position, captures that win material with the most valuable
+// victims first, killer moves, the counter move, the other quiet moves by history, then captures
+// that lose material. Moves are only generated once the stored move has been tried, as it is often
+// all that needs to be searched.
+pub struct MovePicker {
+    stage: Stage,
+    hash_move: Option<Move>,
+    killers: [Option<Move>; 2],
+    killer_index: usize,
+    counter: Option<Move>,
+    // Sorted with the move to play next at the end
+    good_captures: Vec<Move>,
+    quiets: Vec<Move>,
+    bad_captures: Vec<Move>,
+    // Quiescence search only wants captures that do not lose material, unless in check
+    captures_only: bool,
+}
+
+impl MovePicker {
+    pub fn new(board: &Board, hash_move: Option<Move>, heuristics: &Heuristics, ply: u32, previous: Option<Move>) -> MovePicker {
+        MovePicker {
+            stage: Stage::HashMove,
+            hash_move: hash_move.filter(|v| is_pseudo_legal(board, *v)),
+            killers: heuristics.killers(ply),
+            killer_index: 0,
+            counter: heuristics.counter(previous),
+            good_captures: Vec::new(),
+            quiets: Vec::new(),
+            bad_captures: Vec::new(),
+            captures_only: false,
+        }
+    }
+
+    // Captures and queen promotions that do not lose material, or every move when in check
+    pub fn quiescence(board: &Board) -> MovePicker {
+        MovePicker {
+            stage: Stage::Generate,
+            hash_move: None,
+            killers: [None; 2],
+            killer_index: 0,
+            counter: None,
+            good_captures: Vec::new(),
+            quiets: Vec::new(),
+            bad_captures: Vec::new(),
+            captures_only: !board.in_check(),
+        }
+    }
+
+    // The board must be in the same position as when the picker was made
+    pub fn next(&mut self, board: &Board, heuristics: &Heuristics) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::Generate;
+                    if self.hash_move.is_some() {
+                        return self.hash_move;
+                    }
+                }
+                Stage::Generate => {
+                    self.generate(board, heuristics);
+                    self.stage = Stage::GoodCaptures;
+                }
+                Stage::GoodCaptures => match self.good_captures.pop() {
+                    Some(_move) if Some(_move) == self.hash_move => {}
+                    Some(_move) => return Some(_move),
+                    None => {
+                        self.stage = if self.captures_only { Stage::Done } else { Stage::Killers };
+                    }
+                },
+                Stage::Killers => {
+                    if self.killer_index == self.killers.len() {
+                        self.stage = Stage::CounterMove;
+                        continue;
+                    }
+                    let killer = self.killers[self.killer_index];
+                    self.killer_index += 1;
+                    if let Some(killer) = killer.filter(|v| self.is_new_quiet(board, *v)) {
+                        return Some(killer);
+                    }
+                }
+                Stage::CounterMove => {
+                    self.stage = Stage::Quiets;
+                    if let Some(counter) = self.counter.filter(|v| !self.killers.contains(&Some(*v)) && self.is_new_quiet(board, *v)) {
+                        return Some(counter);
+                    }
+                }
+                Stage::Quiets => match self.quiets.pop() {
+                    Some(_move) if self.already_tried(_move) => {}
+                    Some(_move) => return Some(_move),
+                    None => self.stage = Stage::BadCaptures,
+                },
+                Stage::BadCaptures => match self.bad_captures.pop() {
+                    Some(_move) if Some(_move) == self.hash_move => {}
+                    Some(_move) => return Some(_move),
+                    None => self.stage = Stage::Done,
+                },
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    fn generate(&mut self, board: &Board, heuristics: &Heuristics) {
+        let mut good_captures = Vec::new();
+        for _move in board.get_possible_moves(&board.turn, false) {
+            if board.is_capture(_move) || _move.promotion == Some(Queen) {
+                if board.see(_move) >= 0 {
+                    good_captures.push((_move, mvv_lva(board, _move)));
+                } else {
+                    self.bad_captures.push(_move);
+                }
+            } else {
+                self.quiets.push(_move);
+            }
+        }
+
+        // Sorted lowest first, as moves are taken off the end
+        good_captures.sort_by_key(|(_, score)| *score);
+        self.good_captures = good_captures.into_iter().map(|(v, _)| v).collect();
+        if self.captures_only {
+            self.quiets.clear();
+            self.bad_captures.clear();
+        } else {
+            self.quiets.sort_by_cached_key(|v| heuristics.history(*v));
+            self.bad_captures.sort_by_cached_key(|v| mvv_lva(board, *v));
+        }
+    }
+
+    // Killers and counter moves come from other positions, so may not be possible here
+    fn is_new_quiet(&self, board: &Board, _move: Move) -> bool {
+        Some(_move) != self.hash_move && !board.is_capture(_move) && _move.promotion != Some(Queen) && is_pseudo_legal(board, _move)
+    }
+
+    fn already_tried(&self, _move: Move) -> bool {
+        Some(_move) == self.hash_move || self.killers.contains(&Some(_move)) || Some(_move) == self.counter
+    }
+}
+
+fn is_pseudo_legal(board: &Board, _move: Move) -> bool {
+    _move.piece.colour == board.turn && board.validate_move(_move)
+}
+
+// Most valuable victim, least valuable attacker: taking a queen with a pawn is tried before taking
+// a pawn with a queen
+pub(super) fn mvv_lva(board: &Board, _move: Move) -> i32 {
+    let victim = match board.pieces[_move.end.index as usize] {
+        Full(victim) => eval::piece_value(victim.variant),
+        Empty if board.is_capture(_move) => eval::piece_value(Pawn),
+        Empty => 0
+    };
+    let promotion = _move.promotion.map_or(0, eval::piece_value);
+    (victim + promotion) * 10 - eval::piece_value(_move.piece.variant) / 10
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Board;
+    use super::{Heuristics, MovePicker};
+
+    fn picked(board: &Board, picker: &mut MovePicker, heuristics: &Heuristics) -> Vec<String> {
+        let mut moves = Vec::new();
+        while let Some(_move) = picker.next(board, heuristics) {
+            moves.push(board.to_san(_move));
+        }
+        moves
+    }
+
+    #[test]
+    fn every_move_once() {
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let mut heuristics = Heuristics::new();
+        let quiet = board.parse_san("a3").unwrap();
+        heuristics.update(quiet, None, &[], 4, 2);
+
+        let hash_move = board.parse_san("Qxf6").unwrap();
+        let mut picker = MovePicker::new(&board, Some(hash_move), &heuristics, 2, None);
+        let mut moves = picked(&board, &mut picker, &heuristics);
+        assert_eq!(moves[0], "Qxf6");
+
+        let mut expected: Vec<String> = board.get_possible_moves(&board.turn, false).into_iter().map(|v| board.to_san(v)).collect();
+        moves.sort();
+        expected.sort();
+        assert_eq!(moves, expected);
+    }
+
+    #[test]
+    fn order() {
+        // Pawn takes queen, rook takes knight, then the killer, the other quiet moves, and taking the
+        // defended pawn last
+        let board = Board::from_fen("4k3/8/1p6/2q5/1P3n2/8/8/K4R2 w - - 0 1").unwrap();
+        let mut heuristics = Heuristics::new();
+        heuristics.update(board.parse_san("Rf2").unwrap(), None, &[], 3, 0);
+
+        let mut picker = MovePicker::new(&board, None, &heuristics, 0, None);
+        let moves = picked(&board, &mut picker, &heuristics);
+        assert_eq!(&moves[..3], &["bxc5", "Rxf4", "Rf2"]);
+        assert_eq!(moves.last().unwrap(), "b5");
+
+        // Quiescence only wants the captures that do not lose material
+        let mut picker = MovePicker::quiescence(&board);
+        assert_eq!(picked(&board, &mut picker, &heuristics), vec!["bxc5", "Rxf4"]);
+    }
+
+    #[test]
+    fn heuristics() {
+        let board = Board::new();
+        let mut heuristics = Heuristics::new();
+        let nf3 = board.parse_san("Nf3").unwrap();
+        let e4 = board.parse_san("e4").unwrap();
+        let d4 = board.parse_san("d4").unwrap();
+
+        heuristics.update(nf3, Some(e4), &[d4, nf3], 5, 1);
+        assert_eq!(heuristics.killers(1), [Some(nf3), None]);
+        assert_eq!(heuristics.counter(Some(e4)), Some(nf3));
+        assert!(heuristics.history(nf3) > 0);
+        assert!(heuristics.history(d4) < 0);
+
+        // The newest killer goes first, and the same move is not kept twice
+        heuristics.update(e4, None, &[], 5, 1);
+        heuristics.update(e4, None, &[], 5, 1);
+        assert_eq!(heuristics.killers(1), [Some(e4), Some(nf3)]);
+
+        heuristics.new_search();
+        assert_eq!(heuristics.killers(1), [None, None]);
+        assert!(heuristics.history(nf3) > 0);
+        heuristics.clear();
+        assert_eq!(heuristics.history(nf3), 0);
+    }
+}
@@ -0,0 +1,29 @@
+use crate::*;
+use super::{Engine, Limits, SearchResult};
+
+// Anything that can choose the moves for one side of a game, so the frontends can play against
+// this crate's engine or another one without caring which
+pub trait Player {
+    fn name(&self) -> String;
+
+    // Called before each game, so that nothing learned in the last one is used in the next
+    fn new_game(&mut self) -> Result<(), String>;
+
+    // Picks a move for the side to move in the current position of the game
+    fn choose_move(&mut self, game: &Game, limits: Limits) -> Result<SearchResult, String>;
+}
+
+impl Player for Engine {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn new_game(&mut self) -> Result<(), String> {
+        Engine::new_game(self);
+        Ok(())
+    }
+
+    fn choose_move(&mut self, game: &Game, limits: Limits) -> Result<SearchResult, String> {
+        Ok(self.search(game.board(), limits))
+    }
+}
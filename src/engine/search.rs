@@ -0,0 +1,250 @@
+use crate::*;
+use super::{evaluate, Engine, INFINITY, MATE, MATE_BOUND, MAX_DEPTH};
+use super::movepick::{mvv_lva, MovePicker};
+use super::tt::Bound;
+
+// How far above beta or below alpha the static score has to be for a move or position not to be
+// worth searching, per half move of depth left
+const REVERSE_FUTILITY_MARGIN: i32 = 120;
+const FUTILITY_MARGIN: i32 = 150;
+
+impl Engine {
+    // Searches every legal move of the root position, with the best move from the last iteration
+    // first. Fills pv with the best line found and returns its score, which is only exact if it is
+    // inside the window. Otherwise pv is left empty if no move reached alpha.
+    pub(super) fn search_root(&mut self, board: &mut Board, moves: &[Move], depth: u32, window: (i32, i32), best: Option<Move>, pv: &mut Vec<Move>) -> i32 {
+        let mut moves = moves.to_vec();
+        let best = best.or_else(|| self.tt.probe(board.key(), 0).and_then(|v| v.best_move));
+        // The root moves are already known to be legal, so they are ordered here rather than picked
+        moves.sort_by_cached_key(|v| if Some(*v) == best { i32::MIN } else { -mvv_lva(board, *v) });
+
+        let (mut alpha, beta) = window;
+        for _move in moves {
+            let mut line = Vec::new();
+            let undo = board.make_move(_move);
+            self.stack.push(Some(_move));
+            let score = -self.negamax(board, depth - 1, 1, -beta, -alpha, &mut line);
+            self.stack.pop();
+            board.unmake_move(_move, undo);
+
+            if self.stopped {
+                break;
+            }
+            if score > alpha {
+                alpha = score;
+                pv.clear();
+                pv.push(_move);
+                pv.extend(line);
+            }
+            if score >= beta {
+                return beta;
+            }
+        }
+
+        if !self.stopped && alpha > window.0 {
+            self.tt.store(board.key(), depth, Bound::Exact, alpha, pv.first().copied(), 0);
+        }
+        alpha
+    }
+
+    // Alpha-beta search, scoring the position for the side to move. Scores at or below alpha mean
+    // the move leading here is no better than one already found, and scores at or above beta mean the
+    // opponent would never allow this position.
+    fn negamax(&mut self, board: &mut Board, depth: u32, ply: u32, mut alpha: i32, beta: i32, pv: &mut Vec<Move>) -> i32 {
+        if board.halfmove_clock >= 100 || board.repetition_count() >= 2 || board.insufficient_material() {
+            return 0;
+        }
+        if depth == 0 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+        if ply >= MAX_DEPTH {
+            return evaluate(board);
+        }
+
+        // A score from the table can be used straight away if it is outside the window, as it only
+        // needs to be good or bad enough. Inside the window it is searched again for the moves of the pv.
+        let key = board.key();
+        let entry = self.tt.probe(key, ply);
+        if let Some(entry) = entry.filter(|v| v.depth >= depth) {
+            match entry.bound {
+                Bound::Exact | Bound::Lower if entry.score >= beta => return beta,
+                Bound::Exact | Bound::Upper if entry.score <= alpha => return alpha,
+                _ => {}
+            }
+        }
+
+        let in_check = board.in_check();
+        // Only needed for pruning, and meaningless when in check
+        let static_eval = if in_check { -INFINITY } else { evaluate(board) };
+        let mate_window = alpha.abs() >= MATE_BOUND || beta.abs() >= MATE_BOUND;
+
+        // Reverse futility: this close to the leaves, a position this far above beta is very unlikely
+        // to drop below it, so the opponent would not allow it
+        if self.options.reverse_futility && !in_check && !mate_window && depth <= 3
+            && static_eval - REVERSE_FUTILITY_MARGIN * depth as i32 >= beta {
+            return beta;
+        }
+
+        // Null move: if the side to move is still above beta after passing, a real move would be
+        // too. Passing is often the best move when only pawns are left, so it is not tried then.
+        // Two null moves in a row would just be the same position searched less deeply.
+        if self.options.null_move && !in_check && !mate_window && depth >= 3 && static_eval >= beta
+            && self.stack.last().is_some_and(|v| v.is_some()) && has_pieces(board, board.turn) {
+            let reduction = 2 + depth / 4;
+            let undo = board.make_null_move();
+            self.stack.push(None);
+            let score = -self.negamax(board, (depth - 1).saturating_sub(reduction), ply + 1, -beta, -beta + 1, &mut Vec::new());
+            self.stack.pop();
+            board.unmake_null_move(undo);
+
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                return beta;
+            }
+        }
+
+        // Futility: close to the leaves, quiet moves are unlikely to bring a position this far
+        // below alpha back up to it
+        let futile = self.options.futility && !in_check && !mate_window && depth <= 2
+            && static_eval + FUTILITY_MARGIN * depth as i32 <= alpha;
+
+        // The move that led here, which the counter move is looked up by
+        let previous = self.stack.last().copied().flatten();
+        let killers = self.heuristics.killers(ply);
+        let mut picker = MovePicker::new(board, entry.and_then(|v| v.best_move), &self.heuristics, ply, previous);
+        let mut quiets = Vec::new();
+        let mut any_legal = false;
+        let mut searched = 0;
+        while let Some(_move) = picker.next(board, &self.heuristics) {
+            let quiet = !board.is_capture(_move) && _move.promotion != Some(Queen);
+            let undo = board.make_move(_move);
+            if board.is_king_threatened(&_move.piece.colour) {
+                board.unmake_move(_move, undo);
+                continue;
+            }
+            any_legal = true;
+
+            let gives_check = board.in_check();
+            if futile && quiet && !gives_check && searched > 0 {
+                board.unmake_move(_move, undo);
+                continue;
+            }
+
+            // Checks are searched a move deeper, as they often lead to something forcing
+            let extension = if self.options.check_extensions && gives_check { 1 } else { 0 };
+            let new_depth = depth - 1 + extension;
+
+            let mut line = Vec::new();
+            self.stack.push(Some(_move));
+            // Late move reductions: with good move ordering, quiet moves this far down the list rarely
+            // turn out best, so they are first searched less deeply, just to check they cannot beat alpha
+            let reduce = self.options.late_move_reductions && depth >= 3 && searched >= 3 && quiet
+                && !in_check && !gives_check && !killers.contains(&Some(_move));
+            let mut score = alpha + 1;
+            if reduce {
+                let reduction = if searched >= 8 { 2 } else { 1 };
+                let reduced = new_depth.saturating_sub(reduction).max(1);
+                score = -self.negamax(board, reduced, ply + 1, -alpha - 1, -alpha, &mut line);
+            }
+            if score > alpha {
+                line.clear();
+                score = -self.negamax(board, new_depth, ply + 1, -beta, -alpha, &mut line);
+            }
+            self.stack.pop();
+            board.unmake_move(_move, undo);
+            searched += 1;
+
+            if self.stopped {
+                return 0;
+            }
+            if quiet {
+                quiets.push(_move);
+            }
+            if score >= beta {
+                if quiet {
+                    self.heuristics.update(_move, previous, &quiets, depth, ply);
+                }
+                self.tt.store(key, depth, Bound::Lower, beta, Some(_move), ply);
+                return beta;
+            }
+            if score > alpha {
+                alpha = score;
+                pv.clear();
+                pv.push(_move);
+                pv.extend(line);
+            }
+        }
+
+        if !any_legal {
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+
+        match pv.first() {
+            Some(best) => self.tt.store(key, depth, Bound::Exact, alpha, Some(*best), ply),
+            None => self.tt.store(key, depth, Bound::Upper, alpha, None, ply),
+        }
+        alpha
+    }
+
+    // Searches only captures and queen promotions until the position is quiet, so that the position
+    // is not scored in the middle of an exchange. The side to move can always choose to stop taking,
+    // so the static score is a lower bound, unless they are in check and have to get out of it.
+    fn quiescence(&mut self, board: &mut Board, ply: u32, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+        if ply >= MAX_DEPTH {
+            return evaluate(board);
+        }
+
+        let in_check = board.in_check();
+        if !in_check {
+            let stand_pat = evaluate(board);
+            if stand_pat >= beta {
+                return beta;
+            }
+            alpha = alpha.max(stand_pat);
+        }
+
+        // Captures that lose material are not worth looking at
+        let mut picker = MovePicker::quiescence(board);
+        let mut any_legal = false;
+        while let Some(_move) = picker.next(board, &self.heuristics) {
+            let undo = board.make_move(_move);
+            if board.is_king_threatened(&_move.piece.colour) {
+                board.unmake_move(_move, undo);
+                continue;
+            }
+            any_legal = true;
+
+            let score = -self.quiescence(board, ply + 1, -beta, -alpha);
+            board.unmake_move(_move, undo);
+
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                return beta;
+            }
+            alpha = alpha.max(score);
+        }
+
+        if in_check && !any_legal {
+            return -MATE + ply as i32;
+        }
+        alpha
+    }
+}
+
+// Whether a colour has anything other than pawns and its king
+fn has_pieces(board: &Board, colour: Colour) -> bool {
+    board.colour_bb(colour) & !(board.piece_bb(colour, Pawn) | board.piece_bb(colour, King)) != 0
+}
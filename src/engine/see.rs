@@ -0,0 +1,141 @@
+use crate::*;
+use crate::bitboard::*;
+
+// Piece values for exchanges, indexed by Piece::index. The king is never really captured, so its
+// value only needs to be larger than everything else.
+const SEE_VALUE: [i32; 6] = [100, 300, 300, 500, 900, 10000];
+
+impl Board {
+    // Static exchange evaluation: how much material the side making the move wins or loses if both
+    // sides keep recapturing on the end square, each with their least valuable piece, for as long as
+    // it pays for them to do so. Pins are not taken into account.
+    pub fn see(&self, _move: Move) -> i32 {
+        let target = _move.end.index;
+        let mut occupied = self.occupied() & !square_bb(_move.start.index);
+
+        let mut gain = [0; 32];
+        gain[0] = match self.pieces[target as usize] {
+            Full(piece) => SEE_VALUE[piece.variant.index()],
+            Empty if self.is_en_passant(_move) => {
+                occupied &= !square_bb(Board::en_passant_capture_square(_move).index);
+                SEE_VALUE[Pawn.index()]
+            }
+            Empty => 0
+        };
+
+        // Value of the piece that is now standing on the target square
+        let mut on_target = match _move.promotion {
+            Some(promotion) => {
+                gain[0] += SEE_VALUE[promotion.index()] - SEE_VALUE[Pawn.index()];
+                SEE_VALUE[promotion.index()]
+            }
+            None => SEE_VALUE[_move.piece.variant.index()]
+        };
+
+        let mut side = !_move.piece.colour;
+        let mut depth = 0;
+        loop {
+            let attackers = moves::attackers(self, target, occupied);
+            let (piece, from) = match self.least_valuable_attacker(attackers, side) {
+                Some(attacker) => attacker,
+                None => break
+            };
+            // The king can only take if the other side has nothing left to take it back with
+            if piece == King && attackers & self.colour_bb(!side) != 0 {
+                break;
+            }
+
+            depth += 1;
+            // What the side capturing now has gained, if the other side stops after this
+            gain[depth] = on_target - gain[depth - 1];
+            on_target = SEE_VALUE[piece.index()];
+            occupied &= !square_bb(from);
+            side = !side;
+
+            if depth == gain.len() - 1 {
+                break;
+            }
+        }
+
+        // Each side only carries on with the exchange if that is better for them than stopping
+        while depth > 0 {
+            gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+            depth -= 1;
+        }
+        gain[0]
+    }
+
+    fn least_valuable_attacker(&self, attackers: Bitboard, colour: Colour) -> Option<(Piece, i32)> {
+        [Pawn, Knight, Bishop, Rook, Queen, King]
+            .into_iter()
+            .find_map(|piece| Indexes(attackers & self.piece_bb(colour, piece)).next().map(|index| (piece, index)))
+    }
+
+    // Squares of the pieces of a colour that the other side can win material by taking
+    pub fn hanging_pieces(&self, colour: Colour) -> Vec<Square> {
+        let mut board = self.clone();
+        let mut hanging: Vec<Square> = self.get_possible_moves(&!colour, true)
+            .into_iter()
+            .filter(|v| self.pieces[v.end.index as usize] != Empty && self.see(*v) > 0 && board.is_legal(*v))
+            .map(|v| v.end)
+            .collect();
+        hanging.sort_by_key(|v| v.index);
+        hanging.dedup();
+        hanging
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Board, Square};
+
+    fn see(fen: &str, san: &str) -> i32 {
+        let board = Board::from_fen(fen).unwrap();
+        board.see(board.parse_san(san).unwrap())
+    }
+
+    fn squares(names: &[&str]) -> Vec<Square> {
+        names.iter().map(|v| v.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn exchanges() {
+        // Undefended pawn
+        assert_eq!(see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "Rxe5"), 100);
+        // Pawn defended by a bishop, with the knight taking first
+        assert!(see("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "Nxe5") < 0);
+        // Pawn takes a defended knight
+        assert_eq!(see("4k3/8/3p4/4n3/3P4/8/8/4K3 w - - 0 1", "dxe5"), 200);
+        // Queen takes a defended pawn
+        assert_eq!(see("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1", "Qxe5+"), -800);
+        // Even trade
+        assert_eq!(see("4k3/4r3/8/4r3/8/8/8/4RK2 w - - 0 1", "Rxe5"), 0);
+        // The rook behind joins in once the one in front has gone
+        assert_eq!(see("4r1k1/8/8/4r3/8/8/4R3/4RK2 w - - 0 1", "Rxe5"), 500);
+        // Not a capture, but moving onto a square the pawn guards
+        assert_eq!(see("4k3/8/3p4/8/3Q4/8/8/4K3 w - - 0 1", "Qe5+"), -900);
+        assert_eq!(see("4k3/8/3p4/8/3Q4/8/8/4K3 w - - 0 1", "Qd5"), 0);
+        // The king can only take back if nothing else can take it afterwards
+        assert_eq!(see("4k3/4n3/8/8/8/8/4R3/5K2 w - - 0 1", "Rxe7+"), -200);
+        assert_eq!(see("4k3/4n3/8/8/8/8/4R3/4RK2 w - - 0 1", "Rxe7+"), 300);
+    }
+
+    #[test]
+    fn special_moves() {
+        // En passant
+        assert_eq!(see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "exd6"), 100);
+        // Promotion wins the difference between a pawn and a queen, unless the queen is taken
+        assert_eq!(see("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a8=Q"), 800);
+        assert_eq!(see("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a8=Q"), -100);
+        assert_eq!(see("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "axb8=Q+"), 1300);
+    }
+
+    #[test]
+    fn hanging() {
+        // The knight is defended, but a pawn can still win it, and nothing defends the rook. The pawn
+        // on d4 can be taken, but only by giving up the rook for it.
+        let board = Board::from_fen("4k3/8/3p4/4n3/1r1P4/2B5/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.hanging_pieces(crate::Black), squares(&["e5", "b4"]));
+        assert!(board.hanging_pieces(crate::White).is_empty());
+    }
+}
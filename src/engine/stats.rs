@@ -0,0 +1,148 @@
+// Results of a match from the point of view of the first engine, and what they say about the
+// difference in strength between the two engines
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Score {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+// Sequential probability ratio test between two hypotheses about the Elo difference: elo0, that
+// the first engine is no better than that, and elo1, that it is at least that much better. Alpha
+// and beta are how often the test may wrongly accept elo1 and elo0 respectively.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SprtVerdict {
+    // The first engine is at least elo1 better
+    AcceptH1,
+    // The first engine is no more than elo0 better
+    AcceptH0,
+    Continue,
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64) -> Sprt {
+        Sprt { elo0, elo1, alpha: 0.05, beta: 0.05 }
+    }
+
+    // Log likelihood ratio limits, below which H0 is accepted and above which H1 is
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    // Log likelihood ratio of H1 against H0, using a normal approximation of the game results
+    pub fn llr(&self, score: Score) -> f64 {
+        let games = score.games() as f64;
+        let variance = score.variance();
+        if score.games() == 0 || variance <= 0.0 {
+            return 0.0;
+        }
+        let (s0, s1) = (expected_score(self.elo0), expected_score(self.elo1));
+        games * (s1 - s0) * (2.0 * score.ratio() - s0 - s1) / (2.0 * variance)
+    }
+
+    pub fn verdict(&self, score: Score) -> SprtVerdict {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            SprtVerdict::AcceptH1
+        } else if llr <= lower {
+            SprtVerdict::AcceptH0
+        } else {
+            SprtVerdict::Continue
+        }
+    }
+}
+
+impl Score {
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    // Points per game, with a draw worth half a win
+    pub fn ratio(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.5;
+        }
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    // Variance of the points from a single game
+    fn variance(&self) -> f64 {
+        let ratio = self.ratio();
+        let games = self.games() as f64;
+        (self.wins as f64 * (1.0 - ratio).powi(2) + self.draws as f64 * (0.5 - ratio).powi(2) + self.losses as f64 * ratio.powi(2)) / games
+    }
+
+    // Elo difference the score suggests, infinite if one engine won every game
+    pub fn elo(&self) -> f64 {
+        elo_from_score(self.ratio())
+    }
+
+    // Half the width of the 95% confidence interval of elo
+    pub fn elo_error(&self) -> f64 {
+        if self.games() == 0 {
+            return f64::INFINITY;
+        }
+        let error = 1.96 * (self.variance() / self.games() as f64).sqrt();
+        let (low, high) = (self.ratio() - error, self.ratio() + error);
+        (elo_from_score(high) - elo_from_score(low)) / 2.0
+    }
+}
+
+// Expected points per game for an engine this much stronger than its opponent
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+fn elo_from_score(score: f64) -> f64 {
+    if score <= 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if score >= 1.0 {
+        return f64::INFINITY;
+    }
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Score, Sprt, SprtVerdict};
+
+    #[test]
+    fn elo() {
+        let even = Score { wins: 10, losses: 10, draws: 20 };
+        assert_eq!(even.elo(), 0.0);
+        assert!(even.elo_error() > 50.0 && even.elo_error() < 150.0);
+
+        // 75% is a little under 200 Elo
+        let score = Score { wins: 60, losses: 10, draws: 30 };
+        assert_eq!(score.ratio(), 0.75);
+        assert!((score.elo() - 190.8).abs() < 0.1);
+
+        // More games give a smaller error
+        let more = Score { wins: 600, losses: 100, draws: 300 };
+        assert!(more.elo_error() < score.elo_error());
+        assert_eq!(Score { wins: 3, losses: 0, draws: 0 }.elo(), f64::INFINITY);
+    }
+
+    #[test]
+    fn sprt() {
+        let sprt = Sprt::new(0.0, 10.0);
+        let (lower, upper) = sprt.bounds();
+        assert!((lower + 2.944).abs() < 0.001);
+        assert!((upper - 2.944).abs() < 0.001);
+
+        assert_eq!(sprt.verdict(Score::default()), SprtVerdict::Continue);
+        assert_eq!(sprt.verdict(Score { wins: 30, losses: 25, draws: 45 }), SprtVerdict::Continue);
+        assert_eq!(sprt.verdict(Score { wins: 1300, losses: 1000, draws: 2000 }), SprtVerdict::AcceptH1);
+        assert_eq!(sprt.verdict(Score { wins: 1000, losses: 1300, draws: 2000 }), SprtVerdict::AcceptH0);
+    }
+}
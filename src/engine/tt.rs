@@ -0,0 +1,207 @@
+use std::mem::size_of;
+use crate::*;
+use super::MATE_BOUND;
+
+// How a stored score relates to the real score of the position. Searches that are cut off early
+// only find out that the score is at least or at most some value.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Bound {
+    Exact,
+    // The score is at least this, as a move was found that was too good for the opponent to allow
+    Lower,
+    // The score is at most this, as no move was good enough
+    Upper,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Entry {
+    // Full key, as many positions share each slot
+    key: u64,
+    pub depth: u32,
+    pub bound: Bound,
+    pub score: i32,
+    pub best_move: Option<Move>,
+    // Search the entry was stored in, so old entries can be replaced first
+    age: u8,
+}
+
+// Remembers the results of searching positions by their Zobrist key, so that a position reached
+// again through a different move order, or in the next iteration, does not need to be searched again
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+    age: u8,
+}
+
+pub const DEFAULT_HASH_SIZE: usize = 16;
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::new(DEFAULT_HASH_SIZE)
+    }
+}
+
+impl TranspositionTable {
+    // Size is in megabytes, and there is always room for at least one entry
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let count = (megabytes * 1024 * 1024 / size_of::<Option<Entry>>()).max(1);
+        TranspositionTable {
+            entries: vec![None; count],
+            age: 0,
+        }
+    }
+
+    pub fn resize(&mut self, megabytes: usize) {
+        *self = TranspositionTable::new(megabytes);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+        self.age = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.iter().all(|v| v.is_none())
+    }
+
+    // Called at the start of each search, so entries from earlier searches are replaced before
+    // ones from this search
+    pub fn new_search(&mut self) {
+        self.age = self.age.wrapping_add(1);
+    }
+
+    // Spreads keys evenly over the table without needing its length to be a power of two
+    fn index(&self, key: u64) -> usize {
+        ((key as u128 * self.entries.len() as u128) >> 64) as usize
+    }
+
+    // Ply is how far the position is from the root of the search, which mate scores are relative to
+    pub fn probe(&self, key: u64, ply: u32) -> Option<Entry> {
+        self.entries[self.index(key)]
+            .filter(|v| v.key == key)
+            .map(|entry| Entry { score: score_from_tt(entry.score, ply), ..entry })
+    }
+
+    // Keeps whichever of the new and old entries is more useful: the new one, unless the old one is
+    // for another position, from this search and searched deeper
+    pub fn store(&mut self, key: u64, depth: u32, bound: Bound, score: i32, best_move: Option<Move>, ply: u32) {
+        let index = self.index(key);
+        if let Some(old) = self.entries[index] {
+            if old.key != key && old.age == self.age && old.depth > depth {
+                return;
+            }
+        }
+
+        // Keep the best move of an earlier search of this position if this search did not find one
+        let best_move = best_move.or_else(|| self.entries[index].filter(|v| v.key == key).and_then(|v| v.best_move));
+
+        self.entries[index] = Some(Entry {
+            key,
+            depth,
+            bound,
+            score: score_to_tt(score, ply),
+            best_move,
+            age: self.age,
+        });
+    }
+
+    // How full the table is in thousandths, from a sample of the entries
+    pub fn hashfull(&self) -> usize {
+        let sample = self.entries.len().min(1000);
+        let used = self.entries[..sample].iter().filter(|v| v.is_some_and(|v| v.age == self.age)).count();
+        used * 1000 / sample
+    }
+}
+
+// Mate scores count from the root of the search, but a stored position can be reached at any
+// distance from the root, so they are stored counting from the position itself instead
+fn score_to_tt(score: i32, ply: u32) -> i32 {
+    if score > MATE_BOUND {
+        score + ply as i32
+    } else if score < -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: u32) -> i32 {
+    if score > MATE_BOUND {
+        score - ply as i32
+    } else if score < -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Board;
+    use crate::engine::MATE;
+    use super::{Bound, TranspositionTable};
+
+    #[test]
+    fn store_and_probe() {
+        let mut tt = TranspositionTable::new(1);
+        assert!(tt.len() > 1000);
+        assert!(tt.is_empty());
+
+        let board = Board::new();
+        let _move = board.legal_moves()[0];
+        tt.store(board.key(), 5, Bound::Lower, 37, Some(_move), 2);
+
+        let entry = tt.probe(board.key(), 2).unwrap();
+        assert_eq!((entry.depth, entry.bound, entry.score, entry.best_move), (5, Bound::Lower, 37, Some(_move)));
+        assert!(tt.probe(board.key() ^ 1, 2).is_none());
+
+        // The best move is kept when the position is stored again without one
+        tt.store(board.key(), 6, Bound::Upper, 10, None, 2);
+        assert_eq!(tt.probe(board.key(), 0).unwrap().best_move, Some(_move));
+
+        tt.clear();
+        assert!(tt.probe(board.key(), 2).is_none());
+    }
+
+    #[test]
+    fn mate_scores() {
+        let mut tt = TranspositionTable::new(1);
+
+        // Mate in 3 half moves from a position 4 half moves into the search is mate in 7 from the root
+        tt.store(42, 3, Bound::Exact, MATE - 7, None, 4);
+        // Reached again 2 half moves into the search, it is now mate in 5 from the root
+        assert_eq!(tt.probe(42, 2).unwrap().score, MATE - 5);
+
+        tt.store(42, 3, Bound::Exact, -MATE + 6, None, 4);
+        assert_eq!(tt.probe(42, 2).unwrap().score, -MATE + 4);
+
+        tt.store(42, 3, Bound::Exact, 150, None, 4);
+        assert_eq!(tt.probe(42, 2).unwrap().score, 150);
+    }
+
+    #[test]
+    fn replacement() {
+        // A table with a single slot, so every key shares it
+        let mut tt = TranspositionTable::new(0);
+        assert_eq!(tt.len(), 1);
+
+        tt.store(1, 8, Bound::Exact, 0, None, 0);
+        // A shallower search of another position does not push out a deeper one from this search
+        tt.store(2, 4, Bound::Exact, 0, None, 0);
+        assert!(tt.probe(1, 0).is_some());
+        // The same position is always updated
+        tt.store(1, 2, Bound::Exact, 0, None, 0);
+        assert_eq!(tt.probe(1, 0).unwrap().depth, 2);
+
+        // Entries from an earlier search are replaced by anything
+        tt.store(1, 8, Bound::Exact, 0, None, 0);
+        tt.new_search();
+        tt.store(2, 1, Bound::Exact, 0, None, 0);
+        assert!(tt.probe(1, 0).is_none());
+        assert!(tt.probe(2, 0).is_some());
+        assert_eq!(tt.hashfull(), 1000);
+    }
+}
@@ -0,0 +1,366 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::*;
+use super::{Limits, Player, SearchResult, MATE};
+
+// How long an engine has to get ready, e.g. after starting up or a new game
+const READY_TIMEOUT: Duration = Duration::from_secs(10);
+// How long past its time limit an engine has to reply with a move, and then again after it has
+// been told to stop
+const MOVE_GRACE: Duration = Duration::from_secs(1);
+// How long an engine has to quit before it is killed
+const QUIT_TIMEOUT: Duration = Duration::from_millis(500);
+// How long an engine searching to a depth or number of nodes has to reply with a move, by default
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Another engine, run as a subprocess and spoken to with the Universal Chess Interface
+pub struct UciEngine {
+    name: String,
+    process: Child,
+    stdin: ChildStdin,
+    // Lines the engine writes, read on another thread so that waiting for them can time out
+    lines: Receiver<String>,
+    // For searches without a time limit
+    search_timeout: Duration,
+    // Replies to searches that were given up on, which are skipped if they turn up later so they are
+    // not taken as the reply to the next search
+    late_replies: usize,
+}
+
+impl UciEngine {
+    // Runs the engine executable at path and waits for it to be ready
+    pub fn start(path: &str, args: &[String]) -> Result<UciEngine, String> {
+        let mut process = Command::new(path)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| format!("Could not run {}: {}", path, err))?;
+
+        let stdin = process.stdin.take().unwrap();
+        let stdout = process.stdout.take().unwrap();
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let sent = match line {
+                    Ok(line) => sender.send(line).is_ok(),
+                    Err(_) => false
+                };
+                if !sent {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = UciEngine {
+            name: path.to_string(),
+            process,
+            stdin,
+            lines,
+            search_timeout: SEARCH_TIMEOUT,
+            late_replies: 0,
+        };
+        engine.send("uci")?;
+        let mut name = None;
+        engine.wait_for("uciok", Some(READY_TIMEOUT), |line| {
+            if let Some(id) = line.strip_prefix("id name ") {
+                name = Some(id.trim().to_string());
+            }
+        })?;
+        if let Some(name) = name {
+            engine.name = name;
+        }
+        engine.ready()?;
+        Ok(engine)
+    }
+
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        self.send(&format!("setoption name {} value {}", name, value))?;
+        self.ready()
+    }
+
+    // How long to wait for a move when the search has no time limit, after which the engine is told
+    // to stop
+    pub fn set_search_timeout(&mut self, timeout: Duration) {
+        self.search_timeout = timeout;
+    }
+
+    fn ready(&mut self) -> Result<(), String> {
+        self.send("isready")?;
+        self.wait_for("readyok", Some(READY_TIMEOUT), |_| {}).map(|_| ())
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", command)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|err| format!("Could not talk to {}: {}", self.name, err))
+    }
+
+    // Reads lines until one starting with the given word, passing every line before it to on_line.
+    // Returns the line that was waited for.
+    fn wait_for(&mut self, word: &str, timeout: Option<Duration>, mut on_line: impl FnMut(&str)) -> Result<String, String> {
+        let deadline = timeout.map(|v| Instant::now() + v);
+        loop {
+            let line = match deadline {
+                Some(deadline) => self.lines.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+                None => self.lines.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match line {
+                Ok(line) if self.late_replies > 0 && line.starts_with("bestmove") => self.late_replies -= 1,
+                Ok(line) if line.split_whitespace().next() == Some(word) => return Ok(line),
+                Ok(line) => on_line(&line),
+                Err(RecvTimeoutError::Timeout) => return Err(format!("{} did not send {} in time", self.name, word)),
+                Err(RecvTimeoutError::Disconnected) => return Err(format!("{} quit unexpectedly", self.name)),
+            }
+        }
+    }
+}
+
+impl Player for UciEngine {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn new_game(&mut self) -> Result<(), String> {
+        self.send("ucinewgame")?;
+        self.ready()
+    }
+
+    // Tells the engine the whole game rather than just the position, so it knows about repetitions
+    fn choose_move(&mut self, game: &Game, limits: Limits) -> Result<SearchResult, String> {
+        let moves: Vec<String> = game.played().iter().map(|v| v.to_string()).collect();
+        let mut position = format!("position fen {}", game.start().to_fen());
+        if !moves.is_empty() {
+            position.push_str(&format!(" moves {}", moves.join(" ")));
+        }
+        self.send(&position)?;
+        self.send(&go_command(limits))?;
+
+        let start = Instant::now();
+        let board = game.board();
+        let mut result = SearchResult {
+            best_move: None,
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            time: Duration::ZERO,
+            pv: Vec::new(),
+        };
+
+        let timeout = limits.time.map_or(self.search_timeout, |v| v + MOVE_GRACE);
+        let reply = match self.wait_for("bestmove", Some(timeout), |line| read_info(board, line, &mut result)) {
+            Ok(reply) => reply,
+            Err(_) if self.process.try_wait().ok().flatten().is_none() => {
+                self.send("stop")?;
+                match self.wait_for("bestmove", Some(MOVE_GRACE), |line| read_info(board, line, &mut result)) {
+                    Ok(reply) => reply,
+                    Err(err) => {
+                        self.late_replies += 1;
+                        return Err(err);
+                    }
+                }
+            }
+            Err(err) => return Err(err),
+        };
+
+        // bestmove <move> [ponder <move>], where the move is 0000 or (none) if there are no legal moves
+        result.best_move = match reply.split_whitespace().nth(1) {
+            Some("0000" | "(none)") | None => None,
+            Some(notation) => Some(board.parse_coordinate(notation)
+                .map_err(|err| format!("{} played {}: {}", self.name, notation, err))?),
+        };
+        if result.pv.first() != result.best_move.as_ref() {
+            result.pv = result.best_move.into_iter().collect();
+        }
+        if result.time.is_zero() {
+            result.time = start.elapsed();
+        }
+        Ok(result)
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let start = Instant::now();
+        while start.elapsed() < QUIT_TIMEOUT {
+            if let Ok(Some(_)) = self.process.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+fn go_command(limits: Limits) -> String {
+    let mut command = String::from("go");
+    if let Some(depth) = limits.depth {
+        command.push_str(&format!(" depth {}", depth));
+    }
+    if let Some(nodes) = limits.nodes {
+        command.push_str(&format!(" nodes {}", nodes));
+    }
+    if let Some(time) = limits.time {
+        command.push_str(&format!(" movetime {}", time.as_millis()));
+    }
+    command
+}
+
+// Fills in whatever an info line says about the search, e.g.
+// info depth 6 score cp 25 nodes 14300 time 27 pv e2e4 e7e5 g1f3
+fn read_info(board: &Board, line: &str, result: &mut SearchResult) {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    if words.first() != Some(&"info") {
+        return;
+    }
+    let number = |index: usize| words.get(index + 1).and_then(|v| v.parse::<i64>().ok());
+
+    for (index, word) in words.iter().enumerate() {
+        match *word {
+            "depth" => result.depth = number(index).map_or(result.depth, |v| v as u32),
+            "nodes" => result.nodes = number(index).map_or(result.nodes, |v| v as u64),
+            "time" => result.time = number(index).map_or(result.time, |v| Duration::from_millis(v as u64)),
+            "score" => match (words.get(index + 1), number(index + 1)) {
+                (Some(&"cp"), Some(score)) => result.score = score as i32,
+                // Mate in moves rather than half moves, negative when being mated
+                (Some(&"mate"), Some(moves)) if moves > 0 => result.score = MATE - (moves as i32 * 2 - 1),
+                (Some(&"mate"), Some(moves)) => result.score = -MATE - moves as i32 * 2,
+                _ => {}
+            },
+            // The pv is always last, and stops at the first move that cannot be read
+            "pv" => {
+                let mut board = board.clone();
+                result.pv.clear();
+                for notation in &words[index + 1..] {
+                    match board.parse_coordinate(notation) {
+                        Ok(_move) => {
+                            board.make_move(_move);
+                            result.pv.push(_move);
+                        }
+                        Err(_) => break
+                    }
+                }
+                break;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use crate::{Board, Game};
+    use crate::engine::{mate_in, Engine, Limits, Player};
+    use super::{read_info, UciEngine};
+
+    // Writes a shell script that pretends to be an engine, answering the handshake and then doing
+    // whatever on_go says when asked for a move
+    fn stand_in(name: &str, on_go: &str, on_stop: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("rust-chess-{}-{}.sh", name, std::process::id()));
+        let script = format!(r#"#!/bin/sh
+while read -r line; do
+    case "$line" in
+        uci) echo "id name Stand-in {name}"; echo "uciok" ;;
+        isready) echo "readyok" ;;
+        go*) {on_go} ;;
+        stop) {on_stop} ;;
+        quit) exit 0 ;;
+    esac
+done
+"#);
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn plays_moves() {
+        let path = stand_in("quick", r#"echo "info depth 3 score cp 42 nodes 1000 time 5 pv e7e5 g1f3"; echo "bestmove e7e5""#, "");
+        let mut engine = UciEngine::start(path.to_str().unwrap(), &[]).unwrap();
+        assert_eq!(engine.name(), "Stand-in quick");
+        engine.new_game().unwrap();
+
+        let mut game = Game::new();
+        game.play_san("e4").unwrap();
+        let result = engine.choose_move(&game, Limits::time(Duration::from_millis(100))).unwrap();
+        assert_eq!(game.board().to_san(result.best_move.unwrap()), "e5");
+        assert_eq!((result.depth, result.score, result.nodes), (3, 42, 1000));
+        assert_eq!(game.board().to_san_line(&result.pv), vec!["e5", "Nf3"]);
+
+        drop(engine);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn timeouts() {
+        // Only answers once told to stop
+        let path = stand_in("slow", ":", r#"echo "bestmove d2d4""#);
+        let mut engine = UciEngine::start(path.to_str().unwrap(), &[]).unwrap();
+        let result = engine.choose_move(&Game::new(), Limits::time(Duration::from_millis(50))).unwrap();
+        assert_eq!(Board::new().to_san(result.best_move.unwrap()), "d4");
+        drop(engine);
+        fs::remove_file(path).unwrap();
+
+        // Never answers at all
+        let path = stand_in("silent", ":", ":");
+        let mut engine = UciEngine::start(path.to_str().unwrap(), &[]).unwrap();
+        assert!(engine.choose_move(&Game::new(), Limits::time(Duration::from_millis(50))).is_err());
+        drop(engine);
+        fs::remove_file(path).unwrap();
+
+        assert!(UciEngine::start("/nonexistent/engine", &[]).is_err());
+    }
+
+    #[test]
+    fn depth_timeouts() {
+        // Searches to a depth are given up on too, rather than waiting forever
+        let path = stand_in("deep", ":", r#"echo "bestmove d2d4""#);
+        let mut engine = UciEngine::start(path.to_str().unwrap(), &[]).unwrap();
+        engine.set_search_timeout(Duration::from_millis(50));
+        let result = engine.choose_move(&Game::new(), Limits::depth(30)).unwrap();
+        assert_eq!(Board::new().to_san(result.best_move.unwrap()), "d4");
+        drop(engine);
+        fs::remove_file(path).unwrap();
+
+        // Answers the first search only well after being told to stop, and the second straight away.
+        // The late answer must not be taken as the answer to the second.
+        let path = stand_in(
+            "late",
+            r#"searches=$((searches + 1)); if [ "$searches" -gt 1 ]; then echo "bestmove e2e4"; fi"#,
+            r#"sleep 1.5; echo "bestmove d2d4""#,
+        );
+        let mut engine = UciEngine::start(path.to_str().unwrap(), &[]).unwrap();
+        engine.set_search_timeout(Duration::from_millis(50));
+        assert!(engine.choose_move(&Game::new(), Limits::depth(30)).is_err());
+        let result = engine.choose_move(&Game::new(), Limits::depth(30)).unwrap();
+        assert_eq!(Board::new().to_san(result.best_move.unwrap()), "e4");
+        drop(engine);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn info() {
+        let board = Board::new();
+        let mut result = Engine::new().search(&board, Limits::depth(1));
+        read_info(&board, "info depth 12 seldepth 20 multipv 1 score mate 3 nodes 5 nps 100 time 2 pv e2e4 e7e5 e9e9 d2d4", &mut result);
+        assert_eq!(mate_in(result.score), Some(3));
+        assert_eq!(result.depth, 12);
+        assert_eq!(result.pv.len(), 2);
+
+        read_info(&board, "info depth 13 score mate -2 upperbound", &mut result);
+        assert_eq!(mate_in(result.score), Some(-2));
+        // Lines that are not info lines are ignored
+        read_info(&board, "bestmove e2e4", &mut result);
+        assert_eq!(result.depth, 13);
+    }
+}
@@ -0,0 +1,121 @@
+use crate::*;
+
+// Why a move could not be read or played
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum MoveError {
+    // Holds the colour whose turn it actually is
+    WrongTurn(Colour),
+    EmptySquare(Square),
+    OffBoard(Coord),
+    // Square index that is not 0 to 63
+    IndexOffBoard(i32),
+    // Start is None when no piece of that type could be found to make the move
+    CannotReach { piece: Piece, start: Option<Square>, end: Square },
+    // Holds the colour whose king would be in check
+    LeavesKingInCheck(Colour),
+    CannotCastle(CastleSide),
+    // Squares of every piece that could make the move
+    Ambiguous(Vec<Square>),
+    PromotionRequired,
+    InvalidPromotion,
+    // Position is the index of the character the problem was found at
+    Notation { notation: String, position: usize, reason: String },
+}
+
+impl MoveError {
+    pub(crate) fn notation(notation: &str, position: usize, reason: &str) -> MoveError {
+        MoveError::Notation { notation: notation.to_string(), position, reason: reason.to_string() }
+    }
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::WrongTurn(colour) => write!(f, "It is currently {:?}'s turn!", colour),
+            MoveError::EmptySquare(square) => write!(f, "There is no piece on {}", square),
+            MoveError::OffBoard(coord) => write!(f, "Row {} column {} is not on the board", coord.row, coord.column),
+            MoveError::IndexOffBoard(index) => write!(f, "Square {} is not on the board, which has squares 0 to 63", index),
+            MoveError::CannotReach { piece, start: Some(start), end } => write!(f, "{:?} on {} cannot move to {}", piece, start, end),
+            MoveError::CannotReach { piece, start: None, end } => write!(f, "No {:?} can move to {}", piece, end),
+            MoveError::LeavesKingInCheck(colour) => write!(f, "{:?} would be in check!", colour),
+            MoveError::CannotCastle(CastleSide::King) => write!(f, "Cannot castle king side"),
+            MoveError::CannotCastle(CastleSide::Queen) => write!(f, "Cannot castle queen side"),
+            MoveError::Ambiguous(squares) => {
+                let squares: Vec<String> = squares.iter().map(|v| v.to_string()).collect();
+                write!(f, "Pieces on {} can all make that move, say which one is moving, e.g. Nbd7", squares.join(", "))
+            }
+            MoveError::PromotionRequired => write!(f, "A promotion piece must be chosen"),
+            MoveError::InvalidPromotion => write!(f, "Only a pawn reaching the last row can be promoted"),
+            MoveError::Notation { notation, position, reason } => {
+                write!(f, "Could not read '{}' at character {}: {}", notation, position + 1, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_str_move, Board, CastleSide, Coord, King, Knight, MoveError, Pawn, Square, White};
+
+    fn square(name: &str) -> Square {
+        name.parse().unwrap()
+    }
+
+    #[test]
+    fn board_errors() {
+        let mut board = Board::new();
+        let coord = |name: &str| square(name).coord;
+
+        assert_eq!(board.attempt_move_with_coords(coord("e7"), coord("e5"), None), Err(MoveError::WrongTurn(White)));
+        assert_eq!(board.attempt_move_with_coords(coord("e4"), coord("e5"), None), Err(MoveError::EmptySquare(square("e4"))));
+        assert_eq!(
+            board.attempt_move_with_coords(coord("e2"), coord("e5"), None),
+            Err(MoveError::CannotReach { piece: Pawn, start: Some(square("e2")), end: square("e5") })
+        );
+        assert_eq!(
+            board.attempt_move_with_coords(coord("e2"), Coord { row: 8, column: 4 }, None),
+            Err(MoveError::OffBoard(Coord { row: 8, column: 4 }))
+        );
+
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/4r3/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.attempt_move_with_coords(coord("e1"), coord("e2"), None).map(|_| ()), Ok(()));
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/5r2/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.attempt_move_with_coords(coord("e1"), coord("f1"), None), Err(MoveError::LeavesKingInCheck(White)));
+
+        let mut board = Board::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.attempt_move_with_coords(coord("a7"), coord("a8"), None), Err(MoveError::PromotionRequired));
+    }
+
+    #[test]
+    fn notation_errors() {
+        let board = Board::from_fen("4k3/8/8/8/8/2N3N1/8/4K2R w K - 0 1").unwrap();
+
+        assert_eq!(board.parse_san("Ne2"), Err(MoveError::Ambiguous(vec![square("c3"), square("g3")])));
+        assert_eq!(parse_str_move("ne2", &board), Err(MoveError::Ambiguous(vec![square("c3"), square("g3")])));
+        assert_eq!(board.parse_san("Nb5").map(|v| v.start), Ok(square("c3")));
+        assert_eq!(board.parse_san("Nd3"), Err(MoveError::CannotReach { piece: Knight, start: None, end: square("d3") }));
+        assert_eq!(board.parse_san("O-O-O"), Err(MoveError::CannotCastle(CastleSide::Queen)));
+        assert_eq!(board.parse_san("Kd1=Q"), Err(MoveError::InvalidPromotion));
+        assert_eq!(parse_str_move("ke1d2q", &board), Err(MoveError::InvalidPromotion));
+        assert_eq!(board.parse_san("Kxd1"), Err(MoveError::Notation { notation: "Kxd1".to_string(), position: 1, reason: "Move is not a capture".to_string() }));
+
+        let error = |notation: &str| match board.parse_san(notation) {
+            Err(MoveError::Notation { position, .. }) => position,
+            other => panic!("Expected a notation error, found {:?}", other),
+        };
+        assert_eq!(error("Ni3"), 1);
+        assert_eq!(error("Nce9"), 3);
+        assert_eq!(error("e8=X"), 3);
+        assert_eq!(error("K"), 1);
+
+        assert_eq!(parse_str_move("kz1", &board), Err(MoveError::Notation { notation: "kz1".to_string(), position: 1, reason: "Expected a file letter, a to h".to_string() }));
+        assert_eq!(parse_str_move("xe1", &board).map_err(|v| v.to_string()), Err("Could not read 'xe1' at character 1: Invalid piece letter".to_string()));
+        assert_eq!(
+            parse_str_move("ke1e1e1", &board).map_err(|v| v.to_string()),
+            Err("Could not read 'ke1e1e1' at character 7: Moves should be like nf3, ng1f3 or pe8q".to_string())
+        );
+        assert_eq!(parse_str_move("kf3", &board), Err(MoveError::CannotReach { piece: King, start: None, end: square("f3") }));
+    }
+}
@@ -0,0 +1,278 @@
+use crate::*;
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+impl Board {
+    // Sets up a position from Forsyth-Edwards Notation, e.g. START_FEN. The two move clocks may be
+    // left off, in which case they default to 0 and 1.
+    pub fn from_fen(fen: &str) -> Result<Board, String> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() != 4 && fields.len() != 6 {
+            return Err(format!("FEN should have 6 fields (or 4 without the move clocks), found {}", fields.len()));
+        }
+
+        let pieces = parse_placement(fields[0])?;
+
+        let turn = match fields[1] {
+            "w" => White,
+            "b" => Black,
+            other => return Err(format!("Side to move should be 'w' or 'b', found '{}'", other))
+        };
+
+        let castling = parse_castling(fields[2], &pieces)?;
+        let en_passant = parse_en_passant(fields[3], turn, &pieces)?;
+
+        let (halfmove_clock, fullmove_number) = if fields.len() == 6 {
+            let halfmove: u32 = fields[4].parse()
+                .map_err(|_| format!("Halfmove clock should be a whole number, found '{}'", fields[4]))?;
+            let fullmove: u32 = fields[5].parse()
+                .map_err(|_| format!("Fullmove number should be a whole number, found '{}'", fields[5]))?;
+            if fullmove == 0 {
+                return Err("Fullmove number starts at 1, found 0".to_string());
+            }
+            (halfmove, fullmove)
+        } else {
+            (0, 1)
+        };
+
+        let mut board = Board::from_pieces(pieces, turn, castling);
+        board.en_passant = en_passant;
+        board.halfmove_clock = halfmove_clock;
+        board.fullmove_number = fullmove_number;
+        // The en passant square is part of the position, so the starting key has to be redone
+        board.key = board.compute_key();
+        board.history = vec![board.key()];
+
+        if board.is_king_threatened(&!turn) {
+            return Err(format!("{:?} is in check, but it is {:?}'s turn", !turn, turn));
+        }
+
+        Ok(board)
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut placement = String::new();
+        for row in 0..8 {
+            if row > 0 {
+                placement.push('/');
+            }
+
+            let mut empty = 0;
+            for column in 0..8 {
+                match self.pieces[row * 8 + column] {
+                    Empty => empty += 1,
+                    Full(piece) => {
+                        if empty > 0 {
+                            placement.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        placement.push(piece_char(&piece));
+                    }
+                }
+            }
+            if empty > 0 {
+                placement.push_str(&empty.to_string());
+            }
+        }
+
+        let turn = match self.turn {
+            White => "w",
+            Black => "b"
+        };
+
+        let mut castling = String::new();
+        for (right, char) in [
+            (self.castling.white_king_side, 'K'),
+            (self.castling.white_queen_side, 'Q'),
+            (self.castling.black_king_side, 'k'),
+            (self.castling.black_queen_side, 'q'),
+        ] {
+            if right {
+                castling.push(char);
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        let en_passant = match self.en_passant {
+            Some(square) => square.to_string(),
+            None => "-".to_string()
+        };
+
+        format!("{} {} {} {} {} {}", placement, turn, castling, en_passant, self.halfmove_clock, self.fullmove_number)
+    }
+}
+
+// Uppercase for white, lowercase for black
+fn piece_char(piece: &ColourPiece) -> char {
+    match piece.colour {
+        White => piece.variant.to_char().to_ascii_uppercase(),
+        Black => piece.variant.to_char()
+    }
+}
+
+// Ranks are listed from 8 down to 1, which is the same order as the board indexes
+fn parse_placement(placement: &str) -> Result<[Space; 64], String> {
+    let ranks: Vec<&str> = placement.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(format!("Piece placement should have 8 ranks separated by '/', found {}", ranks.len()));
+    }
+
+    let mut pieces = [Empty; 64];
+    for (row, rank) in ranks.iter().enumerate() {
+        let rank_number = 8 - row;
+        let mut column = 0;
+
+        for char in rank.chars() {
+            if let Some(skip) = char.to_digit(10).filter(|v| (1..=8).contains(v)) {
+                column += skip as usize;
+                continue;
+            }
+
+            let colour = if char.is_ascii_uppercase() { White } else { Black };
+            let variant = match char.to_ascii_lowercase() {
+                'p' => Pawn,
+                'n' => Knight,
+                'b' => Bishop,
+                'r' => Rook,
+                'q' => Queen,
+                'k' => King,
+                _ => return Err(format!("Invalid character '{}' in rank {}", char, rank_number))
+            };
+
+            if variant == Pawn && (rank_number == 1 || rank_number == 8) {
+                return Err(format!("Pawn on rank {}, where pawns cannot be", rank_number));
+            }
+            if column < 8 {
+                pieces[row * 8 + column] = Full(ColourPiece { variant, colour });
+            }
+            column += 1;
+        }
+
+        if column != 8 {
+            return Err(format!("Rank {} should have 8 squares, found {}", rank_number, column));
+        }
+    }
+
+    for colour in [White, Black] {
+        let kings = pieces.iter().filter(|v| **v == Full(ColourPiece { variant: King, colour })).count();
+        if kings != 1 {
+            return Err(format!("{:?} should have exactly 1 king, found {}", colour, kings));
+        }
+    }
+
+    Ok(pieces)
+}
+
+fn parse_castling(castling: &str, pieces: &[Space; 64]) -> Result<CastlingRights, String> {
+    let mut rights = CastlingRights::none();
+    if castling == "-" {
+        return Ok(rights);
+    }
+
+    for char in castling.chars() {
+        let (colour, side) = match char {
+            'K' => (White, CastleSide::King),
+            'Q' => (White, CastleSide::Queen),
+            'k' => (Black, CastleSide::King),
+            'q' => (Black, CastleSide::Queen),
+            _ => return Err(format!("Invalid character '{}' in castling rights '{}'", char, castling))
+        };
+        if rights.has(colour, side) {
+            return Err(format!("Castling right '{}' is repeated", char));
+        }
+
+        // The right is meaningless, and would confuse move generation, without the pieces in place
+        let row = home_row(&colour) as usize;
+        if pieces[row * 8 + 4] != Full(ColourPiece { variant: King, colour })
+            || pieces[row * 8 + side.rook_column() as usize] != Full(ColourPiece { variant: Rook, colour }) {
+            return Err(format!("Castling right '{}' needs the {:?} king and rook on their starting squares", char, colour));
+        }
+
+        match char {
+            'K' => rights.white_king_side = true,
+            'Q' => rights.white_queen_side = true,
+            'k' => rights.black_king_side = true,
+            _ => rights.black_queen_side = true,
+        }
+    }
+
+    Ok(rights)
+}
+
+fn parse_en_passant(en_passant: &str, turn: Colour, pieces: &[Space; 64]) -> Result<Option<Square>, String> {
+    if en_passant == "-" {
+        return Ok(None);
+    }
+
+    let square: Square = en_passant.parse()
+        .map_err(|_| format!("En passant square should be a square like e3 or '-', found '{}'", en_passant))?;
+
+    // The square is behind a pawn of the side that just moved
+    let (row, pawn_row, rank) = match turn {
+        White => (2, 3, 6),
+        Black => (5, 4, 3)
+    };
+    if square.coord.row != row {
+        return Err(format!("En passant square {} should be on rank {} when {:?} is to move", en_passant, rank, turn));
+    }
+    let pawn = pieces[(pawn_row * 8 + square.coord.column) as usize];
+    if pieces[square.index as usize] != Empty || pawn != Full(ColourPiece { variant: Pawn, colour: !turn }) {
+        return Err(format!("En passant square {} does not have a pawn that has just moved past it", en_passant));
+    }
+
+    Ok(Some(square))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Board, START_FEN};
+
+    #[test]
+    fn round_trip() {
+        for fen in [
+            START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 12 10",
+        ] {
+            assert_eq!(Board::from_fen(fen).unwrap().to_fen(), fen);
+        }
+
+        assert_eq!(Board::new().to_fen(), START_FEN);
+        assert_eq!(Board::from_fen("8/8/8/4k3/8/8/8/4K3 w - -").unwrap().to_fen(), "8/8/8/4k3/8/8/8/4K3 w - - 0 1");
+    }
+
+    #[test]
+    fn clocks_follow_moves() {
+        let mut board = Board::new();
+        for _move in ["e2e4", "e7e5", "g1f3"] {
+            let _move = board.legal_moves().into_iter().find(|v| v.to_string() == _move).unwrap();
+            board.move_piece(_move).unwrap();
+        }
+        assert_eq!(board.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+    }
+
+    #[test]
+    fn errors() {
+        let error = |fen: &str| Board::from_fen(fen).unwrap_err();
+
+        assert_eq!(error("8/8/8/8/8/8/8/8 w - - 0"), "FEN should have 6 fields (or 4 without the move clocks), found 5");
+        assert_eq!(error("8/8/8/4k3/8/8/4K3 w - - 0 1"), "Piece placement should have 8 ranks separated by '/', found 7");
+        assert_eq!(error("8/8/8/4k4/8/8/8/4K3 w - - 0 1"), "Rank 5 should have 8 squares, found 9");
+        assert_eq!(error("8/8/8/4x3/8/8/8/4K3 w - - 0 1"), "Invalid character 'x' in rank 5");
+        assert_eq!(error("8/8/8/8/8/8/8/4K3 w - - 0 1"), "Black should have exactly 1 king, found 0");
+        assert_eq!(error("4k3/8/8/8/8/8/8/P3K3 w - - 0 1"), "Pawn on rank 1, where pawns cannot be");
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 x - - 0 1"), "Side to move should be 'w' or 'b', found 'x'");
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w K - 0 1"), "Castling right 'K' needs the White king and rook on their starting squares");
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K2R w KK - 0 1"), "Castling right 'K' is repeated");
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w - e9 0 1"), "En passant square should be a square like e3 or '-', found 'e9'");
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w - e3 0 1"), "En passant square e3 should be on rank 6 when White is to move");
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w - e6 0 1"), "En passant square e6 does not have a pawn that has just moved past it");
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w - - x 1"), "Halfmove clock should be a whole number, found 'x'");
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w - - 0 0"), "Fullmove number starts at 1, found 0");
+        assert_eq!(error("4k2R/8/8/8/8/8/8/4K3 w - - 0 1"), "Black is in check, but it is White's turn");
+    }
+}
@@ -0,0 +1,348 @@
+use crate::*;
+
+// A game as a tree of positions, so that moves can be taken back and replayed. Playing a different
+// move after an undo starts a new branch, and the old moves are kept as a variation.
+#[derive(Clone, Debug)]
+pub struct Game {
+    // nodes[0] is the starting position
+    nodes: Vec<Node>,
+    current: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    // Move that led to this position, None for the starting position
+    played: Option<(Move, String)>,
+    board: Board,
+    state: GameState,
+    ply: usize,
+    parent: Option<usize>,
+    // The first child is the main line, the rest are variations
+    children: Vec<usize>,
+    // Child that redo goes to, which is whichever was played most recently
+    redo: Option<usize>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
+}
+
+impl Game {
+    pub fn new() -> Game {
+        Game::from_board(Board::new())
+    }
+
+    pub fn from_board(board: Board) -> Game {
+        let state = board.clone().check_mate();
+        Game {
+            nodes: vec![Node {
+                played: None,
+                board,
+                state,
+                ply: 0,
+                parent: None,
+                children: Vec::new(),
+                redo: None,
+            }],
+            current: 0,
+        }
+    }
+
+    // Builds the game tree from a PGN game, including its variations, and goes to the end of the main line
+    pub fn from_pgn(pgn: &PgnGame) -> Result<Game, String> {
+        let mut game = Game::from_board(pgn.start_board()?);
+        game.add_pgn_line(&pgn.moves)?;
+        Ok(game)
+    }
+
+    fn add_pgn_line(&mut self, line: &[PgnMove]) -> Result<(), String> {
+        for pgn_move in line {
+            let before = self.current;
+            self.play_san(&pgn_move.san).map_err(|err| pgn::illegal_move(self.board(), &pgn_move.san, err))?;
+            let main = self.current;
+
+            // Variations replace the move, so they branch off from the position before it
+            for variation in &pgn_move.variations {
+                self.current = before;
+                self.add_pgn_line(variation)?;
+            }
+            self.nodes[before].redo = Some(main);
+            self.current = main;
+        }
+        Ok(())
+    }
+
+    // The current position
+    pub fn board(&self) -> &Board {
+        &self.nodes[self.current].board
+    }
+
+    pub fn state(&self) -> GameState {
+        self.nodes[self.current].state
+    }
+
+    // Number of moves played to reach the current position
+    pub fn ply(&self) -> usize {
+        self.nodes[self.current].ply
+    }
+
+    // The last move played, with its SAN
+    pub fn last_move(&self) -> Option<(Move, &str)> {
+        self.nodes[self.current].played.as_ref().map(|(_move, san)| (*_move, san.as_str()))
+    }
+
+    // Plays a move from the current position. Any moves that had been undone are kept as a variation,
+    // unless this is the same move, in which case it is the same as redo.
+    pub fn play(&mut self, _move: Move) -> Result<GameState, MoveError> {
+        let existing = self.nodes[self.current].children
+            .iter()
+            .find(|v| self.nodes[**v].played.as_ref().map(|(played, _)| *played) == Some(_move))
+            .copied();
+        if let Some(child) = existing {
+            self.nodes[self.current].redo = Some(child);
+            self.current = child;
+            return Ok(self.state());
+        }
+
+        // Checked first, so a move that is not allowed is not written out as SAN for nothing
+        let mut board = self.board().clone();
+        let state = board.move_piece(_move)?;
+        let san = self.board().to_san(_move);
+
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            played: Some((_move, san)),
+            board,
+            state,
+            ply: self.ply() + 1,
+            parent: Some(self.current),
+            children: Vec::new(),
+            redo: None,
+        });
+        self.nodes[self.current].children.push(index);
+        self.nodes[self.current].redo = Some(index);
+        self.current = index;
+
+        Ok(state)
+    }
+
+    pub fn play_san(&mut self, san: &str) -> Result<GameState, MoveError> {
+        let _move = self.board().parse_san(san)?;
+        self.play(_move)
+    }
+
+    // Returns false if already at the start
+    pub fn undo(&mut self) -> bool {
+        match self.nodes[self.current].parent {
+            Some(parent) => {
+                self.current = parent;
+                true
+            }
+            None => false
+        }
+    }
+
+    // Returns false if there is no undone move to play again
+    pub fn redo(&mut self) -> bool {
+        match self.nodes[self.current].redo {
+            Some(child) => {
+                self.current = child;
+                true
+            }
+            None => false
+        }
+    }
+
+    // Goes to the position after the given number of moves along the current line, which carries on
+    // past the current position through any undone moves. Returns false if the line is not that long.
+    pub fn jump_to_ply(&mut self, ply: usize) -> bool {
+        let mut node = 0;
+        for _ in 0..ply {
+            match self.nodes[node].redo {
+                Some(child) => node = child,
+                None => return false
+            }
+        }
+        self.current = node;
+        true
+    }
+
+    // The position the game started from
+    pub fn start(&self) -> &Board {
+        &self.nodes[0].board
+    }
+
+    // Moves played from the start to reach the current position
+    pub fn played(&self) -> Vec<Move> {
+        let mut played = Vec::new();
+        let mut node = self.current;
+        while let Some(parent) = self.nodes[node].parent {
+            if let Some((_move, _)) = &self.nodes[node].played {
+                played.push(*_move);
+            }
+            node = parent;
+        }
+        played.reverse();
+        played
+    }
+
+    // Every move of the current line with its SAN, including undone moves that redo would replay
+    pub fn line(&self) -> Vec<(Move, &str)> {
+        let mut line = Vec::new();
+        let mut node = 0;
+        while let Some(child) = self.nodes[node].redo {
+            if let Some((_move, san)) = &self.nodes[child].played {
+                line.push((*_move, san.as_str()));
+            }
+            node = child;
+        }
+        line
+    }
+
+    // Moves that have been played from the current position, main line first
+    pub fn next_moves(&self) -> Vec<(Move, &str)> {
+        self.nodes[self.current].children
+            .iter()
+            .filter_map(|v| self.nodes[*v].played.as_ref())
+            .map(|(_move, san)| (*_move, san.as_str()))
+            .collect()
+    }
+
+    // The whole tree as a PGN game, with every branch as a variation
+    pub fn to_pgn(&self) -> PgnGame {
+        let mut pgn = PgnGame::new();
+        pgn.set_start(&self.nodes[0].board);
+        pgn.moves = self.pgn_line(0);
+
+        let mut end = 0;
+        while let Some(child) = self.nodes[end].children.first() {
+            end = *child;
+        }
+        pgn.set_result(self.nodes[end].state);
+
+        pgn
+    }
+
+    // The main line onwards from a node, with the other children as variations of each move
+    fn pgn_line(&self, from: usize) -> Vec<PgnMove> {
+        let mut line = Vec::new();
+        let mut node = from;
+
+        while let Some(main) = self.nodes[node].children.first() {
+            let mut pgn_move = PgnMove::new(self.san(*main));
+            for other in &self.nodes[node].children[1..] {
+                let mut variation = vec![PgnMove::new(self.san(*other))];
+                variation.extend(self.pgn_line(*other));
+                pgn_move.variations.push(variation);
+            }
+            line.push(pgn_move);
+            node = *main;
+        }
+
+        line
+    }
+
+    fn san(&self, node: usize) -> &str {
+        self.nodes[node].played.as_ref().map(|(_, san)| san.as_str()).unwrap_or("")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Board, Game, GameState, PgnGame};
+
+    fn play(game: &mut Game, moves: &[&str]) {
+        for san in moves {
+            game.play_san(san).unwrap();
+        }
+    }
+
+    fn line(game: &Game) -> Vec<&str> {
+        game.line().into_iter().map(|(_, san)| san).collect()
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut game = Game::new();
+        assert!(!game.undo());
+        assert!(!game.redo());
+
+        play(&mut game, &["e4", "e5", "Nf3"]);
+        assert_eq!(game.ply(), 3);
+        assert_eq!(game.last_move().map(|(_, san)| san), Some("Nf3"));
+
+        assert!(game.undo());
+        assert!(game.undo());
+        assert_eq!(game.ply(), 1);
+        assert_eq!(game.board().to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        assert_eq!(line(&game), vec!["e4", "e5", "Nf3"]);
+        assert_eq!(game.start().to_san_line(&game.played()), vec!["e4"]);
+
+        assert!(game.redo());
+        assert!(game.redo());
+        assert!(!game.redo());
+        assert_eq!(game.board().to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+
+        assert!(game.jump_to_ply(0));
+        assert_eq!(game.board(), &Board::new());
+        assert!(game.jump_to_ply(2));
+        assert_eq!(game.last_move().map(|(_, san)| san), Some("e5"));
+        assert!(!game.jump_to_ply(4));
+        assert_eq!(game.ply(), 2);
+    }
+
+    #[test]
+    fn branches() {
+        let mut game = Game::new();
+        play(&mut game, &["e4", "e5", "Nf3", "Nc6"]);
+
+        game.jump_to_ply(2);
+        play(&mut game, &["Bc4"]);
+        assert_eq!(line(&game), vec!["e4", "e5", "Bc4"]);
+        assert_eq!(game.ply(), 3);
+
+        // Playing the old move again goes back into the old branch rather than making a new one
+        game.undo();
+        assert_eq!(game.next_moves().into_iter().map(|(_, san)| san).collect::<Vec<&str>>(), vec!["Nf3", "Bc4"]);
+        play(&mut game, &["Nf3"]);
+        assert_eq!(line(&game), vec!["e4", "e5", "Nf3", "Nc6"]);
+
+        assert!(game.to_pgn().to_string().ends_with("\n1. e4 e5 2. Nf3 (2. Bc4) 2... Nc6 *\n"));
+    }
+
+    #[test]
+    fn illegal_moves() {
+        let mut game = Game::new();
+        assert!(game.play_san("e5").is_err());
+        assert_eq!(game.ply(), 0);
+
+        play(&mut game, &["f3", "e5", "g4"]);
+        assert_eq!(game.play_san("Qh4#"), Ok(GameState::Checkmate(crate::White)));
+        assert_eq!(game.state(), GameState::Checkmate(crate::White));
+        game.undo();
+        assert_eq!(game.state(), GameState::Playing);
+        assert_eq!(game.to_pgn().result, "0-1");
+    }
+
+    #[test]
+    fn pgn() {
+        let pgn = PgnGame::parse("1. e4 e5 (1... c5 2. Nf3 (2. c3) 2... d6) 2. Nf3 (2. Bc4 Nf6) 2... Nc6 *").unwrap();
+        let mut game = Game::from_pgn(&pgn).unwrap();
+        assert_eq!(line(&game), vec!["e4", "e5", "Nf3", "Nc6"]);
+        assert_eq!(game.ply(), 4);
+        assert_eq!(game.to_pgn().moves, pgn.moves);
+
+        game.jump_to_ply(1);
+        assert_eq!(game.next_moves().into_iter().map(|(_, san)| san).collect::<Vec<&str>>(), vec!["e5", "c5"]);
+
+        let from_position = Game::from_pgn(&PgnGame::parse("[FEN \"4k3/8/8/8/8/8/8/R3K3 w Q - 0 1\"]\n1. O-O-O *").unwrap()).unwrap();
+        assert_eq!(from_position.board().to_fen(), "4k3/8/8/8/8/8/8/2KR4 b - - 1 1");
+        // Reported the same way as when the main line is played
+        let error = Game::from_pgn(&PgnGame::parse("1. e4 e5 2. Ke3 *").unwrap()).unwrap_err();
+        assert_eq!(error, "Illegal move 2. Ke3: No King can move to e3");
+        let error = Game::from_pgn(&PgnGame::parse("1. e4 e5 (1... e4) *").unwrap()).unwrap_err();
+        assert_eq!(error, "Illegal move 1... e4: No Pawn can move to e4");
+    }
+}
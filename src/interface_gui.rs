@@ -1,13 +1,16 @@
-use std::cell::RefCell;
-use std::ops::{Add, Sub};
+use std::ops::Add;
+use std::time::Duration;
 use chess::*;
+use chess::engine::{describe_score, evaluate_for, Limits, Player};
 use eframe::egui;
 use eframe::egui::{InnerResponse, Vec2};
 use egui_extras::RetainedImage;
 
-pub fn launch_gui() {
+// The engine is whoever the player plays against, which is this crate's engine unless another one
+// was asked for
+pub fn launch_gui(engine: Box<dyn Player>) {
     let options = eframe::NativeOptions {
-        initial_window_size: Some(Vec2::new(480.0, 480.0)),
+        initial_window_size: Some(Vec2::new(640.0, 480.0)),
         resizable: false,
         ..Default::default()
     };
@@ -15,7 +18,7 @@ pub fn launch_gui() {
     eframe::run_native(
         "Rust Chess",
         options,
-        Box::new(|_cc| Box::new(App::default())),
+        Box::new(|_cc| Box::new(App::new(engine))),
     )
 }
 
@@ -97,18 +100,30 @@ impl Default for Assets {
     }
 }
 
+// How long the engine thinks for each move. The window does not respond while it is thinking.
+const ENGINE_TIME: Duration = Duration::from_secs(1);
+
 struct App {
-    board: Board,
+    game: Game,
+    engine: Box<dyn Player>,
+    // Side the engine plays, if any
+    engine_colour: Option<Colour>,
+    // Score and line from the engine's last move
+    analysis: String,
     assets: Assets,
-    selected: Option<Square>,
+    // Pawn move waiting for the player to choose a promotion piece
+    promoting: Option<Move>,
 }
 
-impl Default for App {
-    fn default() -> Self {
+impl App {
+    fn new(engine: Box<dyn Player>) -> Self {
         Self {
-            board: Board::new(),
+            game: Game::new(),
+            engine,
+            engine_colour: None,
+            analysis: String::new(),
             assets: Assets::default(),
-            selected: None,
+            promoting: None,
         }
     }
 }
@@ -167,8 +182,109 @@ impl App {
         }
     }
 
+    fn make_move(&mut self, new_move: Move) {
+        let result = self.game.play(new_move);
+        match result {
+            Ok(state) => println!("{state:?}"),
+            Err(msg) => println!("{msg}")
+        };
+    }
+
+    fn engine_move(&mut self) {
+        let board = self.game.board().clone();
+        let result = match self.engine.choose_move(&self.game, Limits::time(ENGINE_TIME)) {
+            Ok(result) => result,
+            Err(err) => {
+                // Stop asking, or it would be asked again every frame
+                self.analysis = err;
+                self.engine_colour = None;
+                return;
+            }
+        };
+        if let Some(_move) = result.best_move {
+            let line = board.to_san_line(&result.pv);
+            self.analysis = format!("{} at depth {}: {}", describe_score(result.score), result.depth, line.join(" "));
+            self.make_move(_move);
+        }
+    }
+
+    fn render_promotion(&mut self, ctx: &egui::Context) {
+        let pending = match self.promoting {
+            Some(pending) => pending,
+            None => return
+        };
+
+        egui::Window::new("Promote to")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for variant in PROMOTION_PIECES {
+                        let space = Space::Full(ColourPiece { variant, colour: pending.piece.colour });
+                        let button = egui::widgets::ImageButton::new(self.get_asset(&space).texture_id(ctx), Vec2::new(50.0, 50.0));
+                        if ui.add(button).clicked() {
+                            self.promoting = None;
+                            self.make_move(Move { promotion: Some(variant), ..pending });
+                        }
+                    }
+                });
+                if ui.button("Cancel").clicked() {
+                    self.promoting = None;
+                }
+            });
+    }
+
+    // Undo and redo buttons, and the moves played so far which can be clicked to go back to that position
+    fn render_moves(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("Undo").clicked() {
+                self.game.undo();
+            }
+            if ui.button("Redo").clicked() {
+                self.game.redo();
+            }
+        });
+        if ui.button("Engine move").clicked() {
+            self.engine_move();
+        }
+        let mut engine_black = self.engine_colour == Some(Colour::Black);
+        if ui.checkbox(&mut engine_black, "Engine plays black").changed() {
+            self.engine_colour = if engine_black { Some(Colour::Black) } else { None };
+        }
+        ui.label(format!("Evaluation for white: {}", describe_score(evaluate_for(self.game.board(), Colour::White))));
+        ui.label(&self.analysis);
+
+        let hanging = self.game.board().hanging_pieces(self.game.board().turn);
+        if !hanging.is_empty() {
+            let squares: Vec<String> = hanging.iter().map(|v| v.to_string()).collect();
+            ui.colored_label(egui::Color32::from_rgb(220, 120, 0), format!("Can be won: {}", squares.join(", ")));
+        }
+        ui.separator();
+
+        let current = self.game.ply();
+        let mut jump = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            let line = self.game.line();
+            for (number, pair) in line.chunks(2).enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}.", number + 1));
+                    for (offset, (_, san)) in pair.iter().enumerate() {
+                        let ply = number * 2 + offset + 1;
+                        if ui.selectable_label(ply == current, *san).clicked() {
+                            jump = Some(ply);
+                        }
+                    }
+                });
+            }
+        });
+        if let Some(ply) = jump {
+            self.game.jump_to_ply(ply);
+        }
+    }
+
     fn render_board(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) -> InnerResponse<()> {
-        let board = &mut self.board;
+        let board = self.game.board();
         let selected_square_id = egui::Id::new("selected_square");
 
         let selected_square_option: Option<Square> = ctx.data().get_temp(selected_square_id);
@@ -177,7 +293,7 @@ impl App {
             None => None
         };
 
-        let pieces = board.pieces;
+        let pieces = *board.pieces();
 
         egui::Grid::new("board")
             .spacing(Vec2::new(0.0, 0.0))
@@ -187,8 +303,6 @@ impl App {
                         ui.end_row();
                     }
 
-                    let selected_index = self.selected.map_or(-1, |square| square.index);
-
                     let piece_size = Vec2::new(50.0, 50.0);
 
                     let square = Square::from_index(index as i32);
@@ -196,7 +310,7 @@ impl App {
 
                     let is_enabled = match &selected_pieces {
                         Some(moves) => moves.contains(&square),
-                        None => self.board.can_square_move(space, &square)
+                        None => self.game.board().can_square_move(space, &square)
                     };
 
                     // So that each button can have a different style.
@@ -236,20 +350,18 @@ impl App {
                         ctx.data().insert_temp(selected_square_id, square);
                     }
                     if ui.input().pointer.any_released() && response.hovered() && is_enabled {
-                        match ctx.data().get_temp::<Square>(selected_square_id) {
-                            Some(start_square) => {
-                                let new_move = Move {
-                                    piece: self.board.piece_at_coord(&start_square.coord).unwrap(),
-                                    start: start_square,
-                                    end: square
-                                };
-                                let result = self.board.move_piece(new_move);
-                                match result {
-                                    Ok(state) => println!("{state:?}"),
-                                    Err(msg) => println!("{msg}")
-                                };
+                        if let Some(start_square) = ctx.data().get_temp::<Square>(selected_square_id) {
+                            let new_move = Move {
+                                piece: self.game.board().piece_at_coord(&start_square.coord).unwrap(),
+                                start: start_square,
+                                end: square,
+                                promotion: None,
+                            };
+                            if Move::is_promotion(&new_move.piece, &new_move.end) {
+                                self.promoting = Some(new_move);
+                            } else {
+                                self.make_move(new_move);
                             }
-                            None => {}
                         }
                     }
 
@@ -263,10 +375,18 @@ impl App {
 }
 
 impl eframe::App for App {
-    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
-        let board = &self.board;
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.render_promotion(ctx);
+
+        if self.engine_colour == Some(self.game.board().turn) && self.game.state() == GameState::Playing && self.promoting.is_none() {
+            self.engine_move();
+        }
+
+        egui::SidePanel::right("moves").resizable(false).show(ctx, |ui| {
+            self.render_moves(ui)
+        });
 
-        let response = egui::CentralPanel::default().show(ctx, |ui| {
+        egui::CentralPanel::default().show(ctx, |ui| {
             self.render_board(ctx, ui)
         });
     }
@@ -0,0 +1,128 @@
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::time::Duration;
+use chess::engine::{openings, play_match, Engine, Limits, MatchSettings, Player, SearchOptions, Sprt, SprtVerdict, UciEngine};
+
+const USAGE: &str = "Usage: chess match [--games <n>] [--time <ms> | --depth <n> | --nodes <n>] [--openings <file>] \
+[--pgn <file>] [--sprt <elo0> <elo1>] [--no-adjudication] <engine> <engine>
+
+Each engine is either builtin, builtin:<parts to switch off> with parts from null-move, lmr, futility,
+reverse-futility, aspiration, check-extensions and all, or the path of an engine that speaks UCI,
+followed by any arguments it needs, e.g. \"./chess uci\".";
+
+// Plays a match between two engines from the command line, writing the games to a PGN file and
+// showing the score after each one
+pub fn run_match(args: &[String]) {
+    if let Err(err) = try_run_match(args) {
+        eprintln!("{}\n\n{}", err, USAGE);
+    }
+}
+
+fn try_run_match(args: &[String]) -> Result<(), String> {
+    let mut settings = MatchSettings::default();
+    let mut pgn_path = String::from("match.pgn");
+    let mut engines = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("Missing value for {}", arg));
+        match arg.as_str() {
+            "--games" => settings.games = number(value()?)?,
+            "--time" => settings.limits = Limits::time(Duration::from_millis(number(value()?)?)),
+            "--depth" => settings.limits = Limits::depth(number(value()?)?),
+            "--nodes" => settings.limits = Limits::nodes(number(value()?)?),
+            "--openings" => {
+                let path = value()?;
+                let text = fs::read_to_string(path).map_err(|err| format!("Could not read {}: {}", path, err))?;
+                settings.openings = openings(&text)?;
+            }
+            "--pgn" => pgn_path = value()?.clone(),
+            "--sprt" => {
+                let elo0 = number(value()?)?;
+                let elo1 = number(args.next().ok_or("Missing elo1 for --sprt")?)?;
+                settings.sprt = Some(Sprt::new(elo0, elo1));
+            }
+            "--no-adjudication" => settings.adjudication = None,
+            _ => engines.push(arg.clone()),
+        }
+    }
+    if engines.len() != 2 {
+        return Err(format!("Expected 2 engines, found {}", engines.len()));
+    }
+
+    let mut first = start_engine(&engines[0])?;
+    let mut second = start_engine(&engines[1])?;
+    let mut pgn_file = File::create(&pgn_path).map_err(|err| format!("Could not create {}: {}", pgn_path, err))?;
+    println!("{} vs {}, {} games", first.name(), second.name(), settings.games);
+
+    let result = play_match(first.as_mut(), second.as_mut(), &settings, |pgn, score| {
+        // Games are written as they finish, so they are kept even if the match is stopped early
+        if let Err(err) = writeln!(pgn_file, "{}", pgn) {
+            eprintln!("Could not write to {}: {}", pgn_path, err);
+        }
+        println!(
+            "Game {} {} vs {}: {} - Score {} - {} - {}",
+            pgn.tag("Round").unwrap_or("?"),
+            pgn.tag("White").unwrap_or("?"),
+            pgn.tag("Black").unwrap_or("?"),
+            pgn.result,
+            score.wins,
+            score.losses,
+            score.draws,
+        );
+    })?;
+
+    let score = result.score;
+    println!("\nScore of {} vs {}: {} - {} - {} [{:.3}] {}", first.name(), second.name(), score.wins, score.losses, score.draws, score.ratio(), score.games());
+    println!("Elo difference: {:.1} +/- {:.1}", score.elo(), score.elo_error());
+    if let (Some(sprt), Some(verdict)) = (settings.sprt, result.sprt) {
+        let (lower, upper) = sprt.bounds();
+        let verdict = match verdict {
+            SprtVerdict::AcceptH1 => "H1 accepted",
+            SprtVerdict::AcceptH0 => "H0 accepted",
+            SprtVerdict::Continue => "no verdict yet",
+        };
+        println!("SPRT ({} to {} Elo): LLR {:.2} ({:.2}, {:.2}), {}", sprt.elo0, sprt.elo1, sprt.llr(score), lower, upper, verdict);
+    }
+    println!("Games written to {}", pgn_path);
+    Ok(())
+}
+
+fn number<T: std::str::FromStr>(text: &str) -> Result<T, String> {
+    text.parse().map_err(|_| format!("Expected a number, not '{}'", text))
+}
+
+// builtin, builtin:<parts to switch off, separated by commas>, or the path of a UCI engine followed
+// by any arguments it needs
+fn start_engine(spec: &str) -> Result<Box<dyn Player>, String> {
+    let switched_off = match spec.strip_prefix("builtin") {
+        Some("") => Vec::new(),
+        Some(parts) if parts.starts_with(':') => parts[1..].split(',').collect(),
+        _ => {
+            let words: Vec<String> = spec.split_whitespace().map(|v| v.to_string()).collect();
+            let path = words.first().ok_or("Engine path is empty")?;
+            return Ok(Box::new(UciEngine::start(path, &words[1..])?));
+        }
+    };
+
+    let mut options = SearchOptions::default();
+    for part in switched_off {
+        match part {
+            "null-move" => options.null_move = false,
+            "lmr" => options.late_move_reductions = false,
+            "futility" => options.futility = false,
+            "reverse-futility" => options.reverse_futility = false,
+            "aspiration" => options.aspiration_windows = false,
+            "check-extensions" => options.check_extensions = false,
+            "all" => options = SearchOptions::none(),
+            _ => return Err(format!("Unknown part of the search '{}'", part)),
+        }
+    }
+
+    // Named after how it was set up, so the two sides can be told apart
+    let mut engine = Engine::new();
+    engine.set_options(options);
+    engine.set_name(&format!("rust-chess ({})", spec));
+    Ok(Box::new(engine))
+}
@@ -1,12 +1,14 @@
 use std::collections::VecDeque;
-use chess::{Space, Board, ColourPiece, Coord, GameState, parse_str_move};
+use chess::{Space, Board, Colour, ColourPiece, Coord, Game, GameState, Move, MoveError, Piece, Square};
+use chess::engine::{describe_score, Limits, Player};
 use chess::Piece::*;
 use chess::Colour::*;
 use std::io;
 use std::io::stdout;
+use std::time::Duration;
 use ansi_term::{Colour as TermColour, Style};
 use crossterm::{event};
-use crossterm::event::MouseEvent;
+use crossterm::event::{KeyCode, KeyEvent, MouseEvent};
 
 // To enable terminal colours
 #[cfg(windows)]
@@ -32,10 +34,11 @@ fn string_board(board: &Board) -> String {
     // Add letters at top
     board_string.push_str(&format!("\n{}", column_label));
 
-    board_string.push_str(&format!("{}", term_other.paint("1|")));
-    board.pieces.iter().enumerate().for_each(|(index, piece)| {
+    // Row 0 is rank 8, so white is at the bottom
+    board_string.push_str(&format!("{}", term_other.paint("8|")));
+    board.pieces().iter().enumerate().for_each(|(index, piece)| {
         if index % 8 == 0 && index != 0 {
-            board_string.push_str(&format!("\n{}", term_other.paint(format!("{}|", (index / 8) + 1))))
+            board_string.push_str(&format!("\n{}", term_other.paint(format!("{}|", 8 - index / 8))))
         } else if index == 0 {}
 
         let piece_char = match piece {
@@ -56,50 +59,165 @@ fn string_board(board: &Board) -> String {
         board_string.push_str(&piece_char);
 
         if (index + 1) % 8 == 0 {
-            board_string.push_str(&format!("{}", term_other.paint(format!("|{}", (index / 8) + 1))));
+            board_string.push_str(&format!("{}", term_other.paint(format!("|{}", 8 - index / 8))));
         }
     });
 
-
-    // Terminal displays top to bottom, but board is bottom to top. So lines must be reversed
-    // board_string = board_string.lines().rev().map(|line| String::from(line) + "\n").collect();
-
     //Add letters at bottom
     board_string.push_str(&format!("\n{}\n", column_label));
 
     board_string
 }
 
-pub fn start_terminal() {
-    if cfg!(windows) {
-        enable_virtual_terminal_processing();
+// Moves of the current line, numbered, with the position being shown in brackets
+fn string_moves(game: &Game) -> String {
+    let mut moves = String::new();
+    for (index, (_, san)) in game.line().iter().enumerate() {
+        if index % 2 == 0 {
+            moves.push_str(&format!("{}. ", index / 2 + 1));
+        }
+        if index + 1 == game.ply() {
+            moves.push_str(&format!("[{}] ", san));
+        } else {
+            moves.push_str(&format!("{} ", san));
+        }
+    }
+    moves.push('\n');
+    moves
+}
+
+// How long the engine thinks for each move
+const ENGINE_TIME: Duration = Duration::from_secs(2);
+
+// Lets the engine pick a move for the side to move and plays it, describing it in msg
+fn play_engine_move(engine: &mut dyn Player, game: &mut Game, msg: &mut String) -> Option<GameState> {
+    let board = game.board().clone();
+    let result = match engine.choose_move(game, Limits::time(ENGINE_TIME)) {
+        Ok(result) => result,
+        Err(err) => {
+            msg.push_str(&format!("{}\n", err));
+            return None;
+        }
+    };
+    let _move = result.best_move?;
+
+    // The line can be empty if the search was cut short before it finished a depth
+    let line = board.to_san_line(&result.pv);
+    msg.push_str(&format!("{} played {} ({} at depth {}: {})\n", engine.name(), board.to_san(_move), describe_score(result.score), result.depth, line.join(" ")));
+    game.play(_move).ok()
+}
+
+// What to say once the game is over, or None if it is still going
+fn end_message(state: GameState) -> Option<String> {
+    match state {
+        GameState::Playing => None,
+        GameState::Checkmate(colour) => Some(format!("{:?} wins!", !colour)),
+        GameState::Stalemate => Some(String::from("Stalemate...")),
+        GameState::Draw(reason) => Some(format!("Draw by {}...", reason)),
+    }
+}
+
+// Warns the side to move about any of their pieces that can be won
+fn string_warnings(board: &Board) -> String {
+    let hanging = board.hanging_pieces(board.turn);
+    if hanging.is_empty() {
+        return String::new();
     }
+    let squares: Vec<String> = hanging.iter().map(|v| v.to_string()).collect();
+    format!("Careful, the pieces on {} can be won\n", squares.join(", "))
+}
 
-    // Enable terminal mouse support
-    crossterm::execute!(stdout(), event::EnableMouseCapture).unwrap();
-    crossterm::terminal::disable_raw_mode().unwrap();  // Re-enable ctrl c to quit
+// Takes back the last move, and the engine's reply as well if it is playing a side, or it would just
+// play again. False if there was nothing to take back.
+fn take_back(game: &mut Game, engine_colour: Option<Colour>) -> bool {
+    if !game.undo() {
+        return false;
+    }
+    if engine_colour == Some(game.board().turn) {
+        game.undo();
+    }
+    true
+}
 
-    const MOUSE_MODE: bool = true;
+// Waits for the player to press the key of the piece they want to promote to
+fn read_promotion() -> Piece {
+    println!("Promote to: (q)ueen, (r)ook, (b)ishop or k(n)ight?");
+    loop {
+        if let Ok(event::Event::Key(KeyEvent { code: KeyCode::Char(char), .. })) = event::read() {
+            match char {
+                'q' => return Queen,
+                'r' => return Rook,
+                'b' => return Bishop,
+                'n' => return Knight,
+                _ => {}
+            }
+        }
+    }
+}
+
+// The engine is whoever the player plays against, which is this crate's engine unless another one
+// was asked for. Moves are made by clicking on the board, or typed at a prompt in text mode.
+pub fn start_terminal(mut engine: Box<dyn Player>, text_mode: bool) {
+    #[cfg(windows)]
+    enable_virtual_terminal_processing();
+
+    if !text_mode {
+        // Enable terminal mouse support
+        crossterm::execute!(stdout(), event::EnableMouseCapture).unwrap();
+        crossterm::terminal::disable_raw_mode().unwrap();  // Re-enable ctrl c to quit
+    }
 
-    let mut premoves = VecDeque::from(vec!["pg6", "pg3", "bh6", "bh3", "nf6", "nf3"]);
-    let mut premoves = VecDeque::new();
+    // e.g. VecDeque::from(vec!["pg6", "pg3", "bh6", "bh3", "nf6", "nf3"]);
+    let mut premoves: VecDeque<&str> = VecDeque::new();
 
-    let mut board = Board::new();
+    let mut game = Game::new();
+    // Side the engine is playing, if any
+    let mut engine_colour = None;
     let mut msg = String::new();
 
     let clear_string = format!("{esc}[2J{esc}[1;1H", esc = 27 as char);
 
-    if MOUSE_MODE {
+    if !text_mode {
         let mut start: Option<Coord> = None;
 
-        println!("{}\n{}{}", clear_string, string_board(&board), msg);
+        println!("{}\n{}{}", clear_string, string_board(game.board()), msg);
+        println!("Press u to undo, r to redo, g for the engine to move and e for it to play this side\n");
         loop {
-            if let Ok(event::Event::Mouse(MouseEvent { kind: event::MouseEventKind::Down(event::MouseButton::Left), row, column, .. })) = event::read() {
+            let event = event::read();
+
+            // Taking back a move also drops the selected piece
+            if let Ok(event::Event::Key(KeyEvent { code: KeyCode::Char(char @ ('u' | 'r')), .. })) = event {
+                let changed = if char == 'u' { take_back(&mut game, engine_colour) } else { game.redo() };
+                if changed {
+                    start = None;
+                    println!("{}\n{}{}{}", clear_string, string_board(game.board()), string_moves(&game), string_warnings(game.board()));
+                    println!("It's {:?}'s turn!\n", game.board().turn);
+                }
+                continue;
+            }
+
+            if let Ok(event::Event::Key(KeyEvent { code: KeyCode::Char(char @ ('g' | 'e')), .. })) = event {
+                start = None;
+                if char == 'e' {
+                    engine_colour = Some(game.board().turn);
+                }
+                let state = play_engine_move(engine.as_mut(), &mut game, &mut msg);
+                println!("{}\n{}{}{}{}", clear_string, string_board(game.board()), string_moves(&game), string_warnings(game.board()), msg);
+                msg.clear();
+                if let Some(message) = state.and_then(end_message) {
+                    println!("{}", message);
+                    break;
+                }
+                println!("It's {:?}'s turn!\n", game.board().turn);
+                continue;
+            }
+
+            if let Ok(event::Event::Mouse(MouseEvent { kind: event::MouseEventKind::Down(event::MouseButton::Left), row, column, .. })) = event {
                 if (3..=10).contains(&row) && (3..=25).contains(&column) {
                     let calculated_row = row - 3;
                     let calculated_column = ((column + 1) / 3) - 1;
 
-                    if start == None {
+                    if start.is_none() {
                         start = Some(Coord {
                             column: calculated_column as i32,
                             row: calculated_row as i32,
@@ -108,71 +226,121 @@ pub fn start_terminal() {
                         // todo: highlight this piece on the board string
                         msg.push_str("Selected...\n");
                     } else {
-                        let start = start.take().unwrap();
+                        let start_coord = start.take().unwrap();
                         let end = Coord {
                             row: calculated_row as i32,
                             column: calculated_column as i32,
                         };
 
-                        let state = board.attempt_move_with_coords(start, end);
+                        let board = game.board();
+                        let promotion = match board.piece_at_coord(&start_coord) {
+                            Some(piece) if Move::is_promotion(&piece, &Square::from_coord(&end)) => Some(read_promotion()),
+                            _ => None
+                        };
+
+                        let _move = Square::try_from(start_coord)
+                            .and_then(|start| Ok((start, Square::try_from(end)?)))
+                            .and_then(|(start, end)| match board.piece_at_coord(&start.coord) {
+                                Some(piece) => Ok(Move { piece, start, end, promotion }),
+                                None => Err(MoveError::EmptySquare(start))
+                            });
+                        let state = _move.and_then(|_move| game.play(_move));
 
+                        let board = game.board();
                         match state {
+                            // Clicking another of your own pieces selects that piece instead
+                            Err(MoveError::CannotReach { .. }) | Err(MoveError::WrongTurn(_)) | Err(MoveError::EmptySquare(_))
+                            if board.piece_at_coord(&end).is_some_and(|v| v.colour == board.turn) => {
+                                start = Some(end);
+                                msg.push_str("Selected...\n");
+                            }
                             Err(err) => msg.push_str(&format!("Could not move: {}\n", err)),
-                            Ok(state) => match state {
-                                GameState::Playing => {}
-                                GameState::Checkmate(colour) => {
-                                    println!("{}\n{}", clear_string, string_board(&board));
-                                    println!("{:?} wins!", !colour);
-                                    break;
-                                }
-                                GameState::Stalemate => {
-                                    println!("{}\n{}", clear_string, string_board(&board));
-                                    println!("Stalemate...");
+                            Ok(state) => {
+                                // The engine replies straight away if it is playing the other side
+                                let state = match state {
+                                    GameState::Playing if engine_colour == Some(board.turn) => {
+                                        play_engine_move(engine.as_mut(), &mut game, &mut msg).unwrap_or(GameState::Playing)
+                                    }
+                                    state => state
+                                };
+                                if let Some(message) = end_message(state) {
+                                    println!("{}\n{}{}", clear_string, string_board(game.board()), msg);
+                                    println!("{}", message);
                                     break;
                                 }
                             }
                         }
                     }
-                    println!("{}\n{}{}", clear_string, string_board(&board), msg);
-                    println!("It's {:?}'s turn!\n", board.turn);
+                    println!("{}\n{}{}{}{}", clear_string, string_board(game.board()), string_moves(&game), string_warnings(game.board()), msg);
+                    println!("It's {:?}'s turn!\n", game.board().turn);
                     msg.clear();
                 }
             };
         }
     } else {
         loop {
+            let board = game.board();
             if cfg!(debug_assertions) {
-                println!("\n{}{}", string_board(&board), msg);
+                println!("\n{}{}{}{}", string_board(board), string_moves(&game), string_warnings(board), msg);
             } else {
-                println!("{}\n{}{}", clear_string, string_board(&board), msg);
+                println!("{}\n{}{}{}{}", clear_string, string_board(board), string_moves(&game), string_warnings(board), msg);
             }
             msg.clear();
-            println!("{:?} Player, enter your next move. Examples: nf3; ng1f3; pe3; pe4; etc", board.turn);
+            println!("{:?} Player, enter your next move, undo / redo, go for the engine to move or engine for it to play this side. Examples: e4; Nf3; exd5; O-O; e8=Q; or ng1f3; pe4; etc", board.turn);
 
             let mut input_buffer: String = String::new();
 
-            if !premoves.is_empty() {
+            if engine_colour == Some(board.turn) {
+                input_buffer.push_str("go");
+            } else if !premoves.is_empty() {
                 input_buffer.push_str(premoves.pop_front().unwrap());
             } else {
-                io::stdin()
+                let read = io::stdin()
                     .read_line(&mut input_buffer)
                     .expect("Failed to read line");
+                // Nothing more will be typed
+                if read == 0 {
+                    break;
+                }
             }
 
             let trimmed = input_buffer.trim();
 
-            match parse_str_move(trimmed, &board) {
+            match trimmed {
+                "undo" => {
+                    if !take_back(&mut game, engine_colour) {
+                        msg = String::from("No moves to undo");
+                    }
+                    continue;
+                }
+                "redo" => {
+                    if !game.redo() {
+                        msg = String::from("No moves to redo");
+                    }
+                    continue;
+                }
+                "engine" | "go" => {
+                    if trimmed == "engine" {
+                        engine_colour = Some(board.turn);
+                    }
+                    if let Some(message) = play_engine_move(engine.as_mut(), &mut game, &mut msg).and_then(end_message) {
+                        println!("{}{}", msg, message);
+                        break;
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            match board.parse_typed_move(trimmed) {
                 Ok(_move) => {
-                    match board.move_piece(_move) {
+                    let san = board.to_san(_move);
+                    match game.play(_move) {
                         Err(err) => msg = format!("Could not move: {}", err),
-                        Ok(state) => match state {
-                            GameState::Playing => {}
-                            GameState::Checkmate(colour) => {
-                                println!("{:?} wins!", !colour);
-                                break;
-                            }
-                            GameState::Stalemate => {
-                                println!("Stalemate...");
+                        Ok(state) => match end_message(state) {
+                            None => msg = format!("{:?} played {}", _move.piece.colour, san),
+                            Some(message) => {
+                                println!("{}", message);
                                 break;
                             }
                         }
@@ -0,0 +1,262 @@
+use std::io;
+use std::io::BufRead;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use chess::{Board, Colour};
+use chess::engine::{mate_in, Engine, Limits, SearchResult, DEFAULT_HASH_SIZE};
+
+const NAME: &str = "rust-chess";
+const AUTHOR: &str = "Tazmondo";
+// Largest transposition table that can be asked for, in megabytes
+const MAX_HASH_SIZE: usize = 4096;
+
+// Plays as an engine for a chess GUI or tournament manager, reading Universal Chess Interface
+// commands from stdin and answering on stdout. Searches run on another thread, so that stop and
+// isready can be answered while they do.
+pub fn start_uci() {
+    let mut uci = Uci::new();
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break
+        };
+        if !uci.command(&line) {
+            break;
+        }
+    }
+    uci.stop_search();
+}
+
+struct Uci {
+    // Taken by the search thread while it runs, and given back when it finishes
+    engine: Option<Engine>,
+    search: Option<JoinHandle<Engine>>,
+    stop: Arc<AtomicBool>,
+    board: Board,
+}
+
+impl Uci {
+    fn new() -> Uci {
+        let engine = Engine::new();
+        Uci {
+            stop: engine.stop_flag(),
+            engine: Some(engine),
+            search: None,
+            board: Board::new(),
+        }
+    }
+
+    // Returns false once the engine should quit. Unknown commands are ignored, as the protocol asks.
+    fn command(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.first().copied() {
+            Some("uci") => {
+                println!("id name {}", NAME);
+                println!("id author {}", AUTHOR);
+                println!("option name Hash type spin default {} min 1 max {}", DEFAULT_HASH_SIZE, MAX_HASH_SIZE);
+                println!("option name Clear Hash type button");
+                println!("uciok");
+            }
+            Some("isready") => println!("readyok"),
+            Some("ucinewgame") => {
+                self.stop_search();
+                self.engine().new_game();
+                self.board = Board::new();
+            }
+            Some("position") => {
+                self.stop_search();
+                match parse_position(&words[1..]) {
+                    Ok(board) => self.board = board,
+                    Err(err) => println!("info string {}", err),
+                }
+            }
+            Some("go") => self.go(&words[1..]),
+            Some("stop") => self.stop_search(),
+            Some("setoption") => self.set_option(&words[1..]),
+            Some("quit") => return false,
+            _ => {}
+        }
+        true
+    }
+
+    // Waits for any search that is running to finish, so the engine can be used
+    fn engine(&mut self) -> &mut Engine {
+        if let Some(search) = self.search.take() {
+            self.engine = Some(search.join().expect("Search thread panicked"));
+        }
+        self.engine.as_mut().unwrap()
+    }
+
+    fn stop_search(&mut self) {
+        if let Some(search) = &self.search {
+            self.stop.store(true, Ordering::Relaxed);
+            // In case it is an infinite search that already finished and is waiting to be stopped
+            search.thread().unpark();
+            self.engine();
+        }
+    }
+
+    fn go(&mut self, words: &[&str]) {
+        self.stop_search();
+        let limits = parse_limits(words, self.board.turn);
+        let infinite = words.contains(&"infinite");
+        let engine = self.engine.take().unwrap();
+        let board = self.board.clone();
+
+        self.search = Some(thread::spawn(move || run_search(engine, board, limits, infinite, |line| println!("{}", line))));
+    }
+
+    // setoption name <name> [value <value>], where the name can be more than one word
+    fn set_option(&mut self, words: &[&str]) {
+        self.stop_search();
+        let value_at = words.iter().position(|v| *v == "value").unwrap_or(words.len());
+        let name = words.get(1..value_at).unwrap_or_default().join(" ");
+        let value = words.get(value_at + 1..).unwrap_or_default().join(" ");
+
+        match name.to_lowercase().as_str() {
+            "hash" => match value.parse::<usize>() {
+                Ok(megabytes) => self.engine().set_hash_size(megabytes.clamp(1, MAX_HASH_SIZE)),
+                Err(_) => println!("info string Hash should be a number of megabytes, not '{}'", value),
+            },
+            "clear hash" => self.engine().new_game(),
+            _ => println!("info string Unknown option '{}'", name),
+        }
+    }
+}
+
+// Searches on the thread it is called from, giving output the info and bestmove lines. The bestmove
+// of an infinite search is not given until it has been told to stop, even if it ends before then
+// because it has found a mate or gone as deep as it can.
+fn run_search(mut engine: Engine, board: Board, limits: Limits, infinite: bool, output: impl Fn(&str)) -> Engine {
+    let result = engine.search_with(&board, limits, |info| output(&info_line(info)));
+    let stop = engine.stop_flag();
+    while infinite && !stop.load(Ordering::Relaxed) {
+        thread::park();
+    }
+    match result.best_move {
+        Some(_move) => output(&format!("bestmove {}", _move)),
+        // What the protocol uses for no move, when the game is already over
+        None => output("bestmove 0000"),
+    }
+    engine
+}
+
+// position [startpos | fen <fen>] [moves <move>...], with moves in coordinate notation, e.g. e2e4
+fn parse_position(words: &[&str]) -> Result<Board, String> {
+    let moves_at = words.iter().position(|v| *v == "moves").unwrap_or(words.len());
+    let mut board = match words.first() {
+        Some(&"startpos") => Board::new(),
+        Some(&"fen") => Board::from_fen(&words[1..moves_at].join(" "))?,
+        _ => return Err("Expected startpos or fen after position".to_string()),
+    };
+
+    for notation in words.iter().skip(moves_at + 1) {
+        let _move = board.parse_coordinate(notation).map_err(|err| err.to_string())?;
+        board.make_move(_move);
+    }
+    Ok(board)
+}
+
+// go [depth <n>] [nodes <n>] [movetime <ms>] [wtime <ms>] [btime <ms>] [winc <ms>] [binc <ms>]
+// [movestogo <n>] [infinite]. With no limits at all the search goes on until it is stopped. If only
+// the opponent's clock is given, and nothing else limits the search, ours is taken to be the same.
+fn parse_limits(words: &[&str], turn: Colour) -> Limits {
+    let value = |name: &str| -> Option<u64> {
+        words.iter()
+            .position(|v| *v == name)
+            .and_then(|index| words.get(index + 1))
+            .and_then(|v| v.parse().ok())
+    };
+    let millis = |name: &str| value(name).map(Duration::from_millis);
+
+    let (white, black) = ((millis("wtime"), millis("winc")), (millis("btime"), millis("binc")));
+    let ((time_left, increment), (other_time_left, other_increment)) = match turn {
+        Colour::White => (white, black),
+        Colour::Black => (black, white),
+    };
+
+    let mut limits = Limits {
+        depth: value("depth").map(|v| v as u32),
+        nodes: value("nodes"),
+        ..Limits::default()
+    };
+    if words.contains(&"infinite") {
+        return limits;
+    }
+    limits.time = match (millis("movetime"), time_left) {
+        (Some(time), _) => Some(time),
+        (None, Some(time_left)) => {
+            Limits::clock(time_left, increment.unwrap_or_default(), value("movestogo").map(|v| v as u32)).time
+        }
+        (None, None) if limits.depth.is_none() && limits.nodes.is_none() => other_time_left.and_then(|time_left| {
+            Limits::clock(time_left, other_increment.unwrap_or_default(), value("movestogo").map(|v| v as u32)).time
+        }),
+        (None, None) => None,
+    };
+    limits
+}
+
+// e.g. info depth 6 score cp 25 nodes 14300 nps 520000 time 27 pv e2e4 e7e5 g1f3
+fn info_line(result: &SearchResult) -> String {
+    let score = match mate_in(result.score) {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", result.score),
+    };
+    let millis = result.time.as_millis() as u64;
+    let nps = result.nodes * 1000 / millis.max(1);
+    let pv: Vec<String> = result.pv.iter().map(|v| v.to_string()).collect();
+    format!("info depth {} score {} nodes {} nps {} time {} pv {}", result.depth, score, result.nodes, nps, millis, pv.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+    use chess::{Board, Colour};
+    use chess::engine::{Engine, Limits};
+    use super::{parse_limits, run_search};
+
+    #[test]
+    fn limits() {
+        let limits = |line: &str| parse_limits(&line.split_whitespace().collect::<Vec<_>>(), Colour::White);
+        assert_eq!(limits("movetime 500").time, Some(Duration::from_millis(500)));
+        assert_eq!(limits("wtime 60000 btime 1000").time, Some(Duration::from_millis(2000)));
+        assert_eq!(limits("infinite wtime 60000").time, None);
+        assert_eq!(limits("").time, None);
+
+        // Only the opponent's clock is given, which is better than searching forever
+        assert_eq!(limits("btime 60000").time, Some(Duration::from_millis(2000)));
+        assert_eq!(limits("btime 60000 binc 2000").time, Some(Duration::from_millis(3500)));
+        // Unless something else limits the search
+        assert_eq!(limits("btime 60000 depth 5"), Limits::depth(5));
+        assert_eq!(limits("btime 60000 nodes 1000"), Limits::nodes(1000));
+        assert_eq!(limits("btime 60000 infinite").time, None);
+    }
+
+    #[test]
+    fn infinite_waits_for_stop() {
+        // Mate in one is found straight away, but the move is only given once the search is stopped
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let engine = Engine::new();
+        let stop = engine.stop_flag();
+        let (sender, lines) = mpsc::channel();
+        let search = thread::spawn(move || run_search(engine, board, Limits::default(), true, |line| sender.send(line.to_string()).unwrap()));
+
+        let mut info = 0;
+        while let Ok(line) = lines.recv_timeout(Duration::from_millis(500)) {
+            assert!(line.starts_with("info"), "{}", line);
+            info += 1;
+        }
+        assert!(info > 0);
+
+        stop.store(true, Ordering::Relaxed);
+        search.thread().unpark();
+        assert_eq!(lines.recv_timeout(Duration::from_secs(5)).unwrap(), "bestmove a1a8");
+        search.join().unwrap();
+    }
+}
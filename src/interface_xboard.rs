@@ -0,0 +1,311 @@
+use std::io;
+use std::io::BufRead;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use chess::{Board, Colour, Game, GameState, Move};
+use chess::engine::{mate_in, Engine, Limits, SearchResult};
+
+const NAME: &str = "rust-chess";
+// Commands that are only for information, or offers and requests that are declined by ignoring them
+const IGNORED: [&str; 15] = [
+    "xboard", "accepted", "rejected", "random", "hard", "easy", "computer", "otim", "draw", "hint", "bk", "name", "rating", "ics", "nps",
+];
+
+// Plays as an engine for XBoard, WinBoard or anything else speaking the Chess Engine Communication
+// Protocol, reading commands from stdin and answering on stdout. As with UCI, searches run on
+// another thread so that commands like ? can interrupt them.
+pub fn start_xboard() {
+    let mut xboard = XBoard::new();
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break
+        };
+        if !xboard.command(&line) {
+            break;
+        }
+    }
+    xboard.cancel_search();
+}
+
+struct XBoard {
+    // Taken by the search thread while it runs, and given back when it finishes along with the
+    // move it played, if it played one
+    engine: Option<Engine>,
+    search: Option<JoinHandle<(Engine, Option<Move>)>>,
+    stop: Arc<AtomicBool>,
+    // Set along with stop when the search should end without playing its move
+    cancel: Arc<AtomicBool>,
+    // Pings that came in while the engine was thinking about its move, which the protocol asks to be
+    // answered after the move. None once there is no move to wait for.
+    pongs: Arc<Mutex<Option<Vec<String>>>>,
+    game: Game,
+    // None in force mode, where moves are only played for both sides and the engine does not think
+    engine_colour: Option<Colour>,
+    // Whether to show the search as it goes
+    post: bool,
+    // Limits from sd and st
+    depth: Option<u32>,
+    move_time: Option<Duration>,
+    // Time control from level: moves per control (0 for the whole game), starting time and increment
+    moves_per_control: u32,
+    base_time: Duration,
+    increment: Duration,
+    // The engine's clock, as last told by the time command
+    time_left: Duration,
+}
+
+impl XBoard {
+    fn new() -> XBoard {
+        let engine = Engine::new();
+        XBoard {
+            stop: engine.stop_flag(),
+            cancel: Arc::new(AtomicBool::new(false)),
+            pongs: Arc::new(Mutex::new(None)),
+            engine: Some(engine),
+            search: None,
+            game: Game::new(),
+            engine_colour: Some(Colour::Black),
+            post: false,
+            depth: None,
+            move_time: None,
+            moves_per_control: 40,
+            base_time: Duration::from_secs(300),
+            increment: Duration::ZERO,
+            time_left: Duration::from_secs(300),
+        }
+    }
+
+    // Returns false once the engine should quit
+    fn command(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let first = match words.first() {
+            Some(first) => *first,
+            None => return true,
+        };
+
+        // These interrupt any search that is running, pings and ignored commands are dealt with
+        // straight away, and the rest wait for it to finish
+        match first {
+            "?" => self.stop_search(),
+            "new" | "force" | "setboard" | "undo" | "remove" | "result" | "quit" => self.cancel_search(),
+            "ping" => {}
+            _ if IGNORED.contains(&first) => {}
+            _ => self.wait_for_search(),
+        }
+
+        match first {
+            "?" => {}
+            _ if IGNORED.contains(&first) => {}
+            "protover" => {
+                println!(
+                    "feature myname=\"{}\" usermove=1 setboard=1 ping=1 memory=1 draw=0 name=0 sigint=0 sigterm=0 colors=0 done=1",
+                    NAME
+                );
+            }
+            "ping" => {
+                let pong = format!("pong {}", words.get(1).unwrap_or(&""));
+                match self.pongs.lock().unwrap().as_mut() {
+                    Some(pongs) => pongs.push(pong),
+                    None => println!("{}", pong),
+                }
+            }
+            "new" => {
+                self.engine().new_game();
+                self.game = Game::new();
+                self.engine_colour = Some(Colour::Black);
+                self.depth = None;
+                self.time_left = self.base_time;
+            }
+            "setboard" => match Board::from_fen(&words[1..].join(" ")) {
+                Ok(board) => self.game = Game::from_board(board),
+                Err(err) => println!("tellusererror Illegal position: {}", err),
+            },
+            "force" | "result" => self.engine_colour = None,
+            "go" => {
+                self.engine_colour = Some(self.game.board().turn);
+                self.think();
+            }
+            "usermove" => match words.get(1) {
+                Some(notation) => self.user_move(notation),
+                None => println!("Error (no move given): {}", line),
+            },
+            "undo" => {
+                self.game.undo();
+            }
+            "remove" => {
+                self.game.undo();
+                self.game.undo();
+            }
+            "level" => match parse_level(&words[1..]) {
+                Some((moves, base, increment)) => {
+                    self.moves_per_control = moves;
+                    self.base_time = base;
+                    self.increment = increment;
+                    self.time_left = base;
+                    self.move_time = None;
+                }
+                None => println!("Error (bad time control): {}", line),
+            },
+            "st" => match words.get(1).and_then(|v| v.parse::<f64>().ok()) {
+                Some(seconds) => self.move_time = Some(Duration::from_secs_f64(seconds.max(0.0))),
+                None => println!("Error (bad time): {}", line),
+            },
+            "sd" => match words.get(1).and_then(|v| v.parse().ok()) {
+                Some(depth) => self.depth = Some(depth),
+                None => println!("Error (bad depth): {}", line),
+            },
+            // In centiseconds
+            "time" => match words.get(1).and_then(|v| v.parse::<u64>().ok()) {
+                Some(time) => self.time_left = Duration::from_millis(time * 10),
+                None => println!("Error (bad time): {}", line),
+            },
+            "memory" => match words.get(1).and_then(|v| v.parse::<usize>().ok()) {
+                Some(megabytes) => self.engine().set_hash_size(megabytes.max(1)),
+                None => println!("Error (bad memory size): {}", line),
+            },
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "quit" => return false,
+            _ => println!("Error (unknown command): {}", first),
+        }
+        true
+    }
+
+    fn user_move(&mut self, notation: &str) {
+        let _move = match self.game.board().parse_coordinate(notation) {
+            Ok(_move) => _move,
+            Err(err) => {
+                println!("Illegal move ({}): {}", err, notation);
+                return;
+            }
+        };
+        match self.game.play(_move) {
+            Ok(GameState::Playing) => {
+                if self.engine_colour == Some(self.game.board().turn) {
+                    self.think();
+                }
+            }
+            Ok(state) => print_result(state),
+            Err(err) => println!("Illegal move ({}): {}", err, notation),
+        }
+    }
+
+    // Starts searching for a move for the side to move, which is played once the search finishes
+    fn think(&mut self) {
+        if self.game.state() != GameState::Playing {
+            return;
+        }
+
+        let mut limits = match self.move_time {
+            Some(time) => Limits::time(time),
+            None => {
+                // Moves the engine still has to make before the clock is topped up again
+                let moves_to_go = match self.moves_per_control {
+                    0 => None,
+                    moves => Some(moves - (self.game.ply() / 2) as u32 % moves),
+                };
+                Limits::clock(self.time_left, self.increment, moves_to_go)
+            }
+        };
+        limits.depth = self.depth;
+
+        let mut engine = self.engine.take().unwrap();
+        let board = self.game.board().clone();
+        let post = self.post;
+        let cancel = self.cancel.clone();
+        cancel.store(false, Ordering::Relaxed);
+        let pongs = self.pongs.clone();
+        *pongs.lock().unwrap() = Some(Vec::new());
+
+        self.search = Some(thread::spawn(move || {
+            let result = engine.search_with(&board, limits, |info| {
+                if post {
+                    println!("{}", thinking_line(info));
+                }
+            });
+            // Held until the pings are answered, so none can come in between the move and them
+            let mut pongs = pongs.lock().unwrap();
+            let _move = result.best_move.filter(|_| !cancel.load(Ordering::Relaxed));
+            if let Some(_move) = _move {
+                println!("move {}", _move);
+                let state = board.clone().move_piece(_move).unwrap();
+                if state != GameState::Playing {
+                    print_result(state);
+                }
+            }
+            for pong in pongs.take().unwrap_or_default() {
+                println!("{}", pong);
+            }
+            (engine, _move)
+        }));
+    }
+
+    // Waits for the search to finish, and plays its move
+    fn wait_for_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            let (engine, _move) = search.join().expect("Search thread panicked");
+            self.engine = Some(engine);
+            if let Some(_move) = _move {
+                self.game.play(_move).unwrap();
+            }
+        }
+    }
+
+    // Makes the engine move straight away with the best move it has found so far
+    fn stop_search(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.wait_for_search();
+    }
+
+    // Ends the search without playing a move
+    fn cancel_search(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        self.stop_search();
+    }
+
+    fn engine(&mut self) -> &mut Engine {
+        self.wait_for_search();
+        self.engine.as_mut().unwrap()
+    }
+}
+
+// level <moves per control> <base> <increment>, where the base is in minutes, or minutes:seconds,
+// and the increment is in seconds
+fn parse_level(words: &[&str]) -> Option<(u32, Duration, Duration)> {
+    if words.len() != 3 {
+        return None;
+    }
+    let moves = words[0].parse().ok()?;
+    let base = match words[1].split_once(':') {
+        Some((minutes, seconds)) => minutes.parse::<u64>().ok()? * 60 + seconds.parse::<u64>().ok()?,
+        None => words[1].parse::<u64>().ok()? * 60,
+    };
+    let increment = words[2].parse::<f64>().ok().filter(|v| *v >= 0.0)?;
+    Some((moves, Duration::from_secs(base), Duration::from_secs_f64(increment)))
+}
+
+fn print_result(state: GameState) {
+    match state {
+        GameState::Playing => {}
+        GameState::Checkmate(Colour::White) => println!("0-1 {{Black mates}}"),
+        GameState::Checkmate(Colour::Black) => println!("1-0 {{White mates}}"),
+        GameState::Stalemate => println!("1/2-1/2 {{Stalemate}}"),
+        GameState::Draw(reason) => println!("1/2-1/2 {{Draw by {}}}", reason),
+    }
+}
+
+// <depth> <score> <time> <nodes> <pv>, with the time in centiseconds. Mates are shown the way
+// XBoard expects them, as 100000 plus the number of moves to mate.
+fn thinking_line(result: &SearchResult) -> String {
+    let score = match mate_in(result.score) {
+        Some(moves) if moves > 0 => 100000 + moves,
+        Some(moves) => -100000 + moves,
+        None => result.score,
+    };
+    let pv: Vec<String> = result.pv.iter().map(|v| v.to_string()).collect();
+    format!("{} {} {} {} {}", result.depth, score, result.time.as_millis() / 10, result.nodes, pv.join(" "))
+}
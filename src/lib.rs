@@ -1,15 +1,39 @@
+mod bitboard;
+mod coordinate;
+pub mod engine;
+mod error;
+mod fen;
+mod game;
 mod moves;
-
+mod perft;
+mod pgn;
+mod san;
+mod zobrist;
+
+use std::fmt;
+use std::str::FromStr;
+pub use error::MoveError;
+pub use fen::START_FEN;
+pub use game::Game;
+pub use pgn::{PgnGame, PgnMove};
+use bitboard::{Bitboard, Indexes, LIGHT_SQUARES, PAWN_ATTACKS};
 use Colour::*;
 use Piece::*;
 use Space::*;
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
 pub enum Colour {
     White,
     Black,
 }
 
+impl Colour {
+    // Position of this colour in per-colour tables
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
 impl std::ops::Not for Colour {
     type Output = Colour;
 
@@ -22,7 +46,7 @@ impl std::ops::Not for Colour {
 }
 
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
 pub enum Piece {
     Pawn,
     Knight,
@@ -32,13 +56,111 @@ pub enum Piece {
     King,
 }
 
+// Pieces a pawn can be promoted to, in order of preference
+pub const PROMOTION_PIECES: [Piece; 4] = [Queen, Rook, Bishop, Knight];
+
+impl Piece {
+    // Position of this piece in per-piece tables
+    fn index(&self) -> usize {
+        *self as usize
+    }
+
+    // Lowercase letter used for the piece in move notation
+    fn to_char(self) -> char {
+        match self {
+            Pawn => 'p',
+            Knight => 'n',
+            Bishop => 'b',
+            Rook => 'r',
+            Queen => 'q',
+            King => 'k',
+        }
+    }
+
+    fn promotion_from_char(char: char) -> Option<Piece> {
+        match char {
+            'q' => Some(Queen),
+            'r' => Some(Rook),
+            'b' => Some(Bishop),
+            'n' => Some(Knight),
+            _ => None
+        }
+    }
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum CastleSide {
     King,
     Queen,
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+impl CastleSide {
+    // Column the rook for this side starts on
+    fn rook_column(&self) -> i32 {
+        match self {
+            CastleSide::King => 7,
+            CastleSide::Queen => 0,
+        }
+    }
+}
+
+// Which castles each colour is still allowed to make. A right is lost for good once the king or
+// that side's rook moves, or the rook is captured on its starting square.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Hash)]
+pub struct CastlingRights {
+    pub white_king_side: bool,
+    pub white_queen_side: bool,
+    pub black_king_side: bool,
+    pub black_queen_side: bool,
+}
+
+impl CastlingRights {
+    pub fn all() -> CastlingRights {
+        CastlingRights {
+            white_king_side: true,
+            white_queen_side: true,
+            black_king_side: true,
+            black_queen_side: true,
+        }
+    }
+
+    pub fn none() -> CastlingRights {
+        CastlingRights::default()
+    }
+
+    pub fn has(&self, colour: Colour, side: CastleSide) -> bool {
+        match (colour, side) {
+            (White, CastleSide::King) => self.white_king_side,
+            (White, CastleSide::Queen) => self.white_queen_side,
+            (Black, CastleSide::King) => self.black_king_side,
+            (Black, CastleSide::Queen) => self.black_queen_side,
+        }
+    }
+
+    pub fn remove(&mut self, colour: Colour, side: CastleSide) {
+        match (colour, side) {
+            (White, CastleSide::King) => self.white_king_side = false,
+            (White, CastleSide::Queen) => self.white_queen_side = false,
+            (Black, CastleSide::King) => self.black_king_side = false,
+            (Black, CastleSide::Queen) => self.black_queen_side = false,
+        }
+    }
+
+    pub fn remove_colour(&mut self, colour: Colour) {
+        self.remove(colour, CastleSide::King);
+        self.remove(colour, CastleSide::Queen);
+    }
+}
+
+// Row the king and rooks of a colour start on
+fn home_row(colour: &Colour) -> i32 {
+    match colour {
+        White => 7,
+        Black => 0
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
 pub struct ColourPiece {
     pub variant: Piece,
     pub colour: Colour,
@@ -58,72 +180,107 @@ impl ColourPiece {
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
 pub enum Space {
     Empty,
     Full(ColourPiece),
 }
 
 // Represents an arbitrary coordinate
-#[derive(PartialEq, Copy, Clone, Debug)]
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
 pub struct Coord {
     pub row: i32,
     pub column: i32,
 }
 
 // Represents a coordinate that actually exists on the board
-#[derive(PartialEq, Copy, Clone, Debug)]
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
 pub struct Square {
     pub coord: Coord,
     pub index: i32,
 }
 
 impl Square {
+    // Panics if the index is not 0 to 63, use try_from for indexes that might be off the board
     pub fn from_index(index: i32) -> Square {
+        Square::try_from(index).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // Panics if the coord is off the board, use try_from for coords that might be
+    pub fn from_coord(coord: &Coord) -> Square {
+        Square::try_from(*coord).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // File letter, 'a' to 'h'
+    pub fn file(&self) -> char {
+        (b'a' + self.coord.column as u8) as char
+    }
+
+    // Rank number, 1 to 8
+    pub fn rank(&self) -> u32 {
+        (8 - self.coord.row) as u32
+    }
+}
+
+impl TryFrom<i32> for Square {
+    type Error = MoveError;
+
+    fn try_from(index: i32) -> Result<Square, MoveError> {
         if !(0..=63).contains(&index) {
-            panic!("Invalid index passed: {}", index)
+            return Err(MoveError::IndexOffBoard(index));
         }
 
-        Square {
+        Ok(Square {
             coord: Coord {
                 column: index % 8,
                 row: index / 8,
             },
             index,
-        }
+        })
     }
+}
 
-    pub fn from_coord(coord: &Coord) -> Square {
-        if coord.column < 0 || coord.column > 7 || coord.row < 0 || coord.row > 7 {
-            panic!("Invalid row and/or column passed:\nrow: {}\ncolumn: {}", coord.row, coord.column)
+impl TryFrom<Coord> for Square {
+    type Error = MoveError;
+
+    fn try_from(coord: Coord) -> Result<Square, MoveError> {
+        if !validate_coord(&coord) {
+            return Err(MoveError::OffBoard(coord));
         }
 
-        Square {
+        Ok(Square {
             index: (coord.row * 8) + coord.column,
-            coord: *coord,
-        }
+            coord,
+        })
     }
+}
 
-    pub fn from_str(coords: &[char]) -> Result<Square, String> {
-        let column = match &coords[0] {
-            'a' | '1' => Ok(0),
-            'b' | '2' => Ok(1),
-            'c' | '3' => Ok(2),
-            'd' | '4' => Ok(3),
-            'e' | '5' => Ok(4),
-            'f' | '6' => Ok(5),
-            'g' | '7' => Ok(6),
-            'h' | '8' => Ok(7),
-            _ => Err("Invalid column letter")
-        }?;
-        let row = coords[1].to_digit(10).ok_or("Row was not a number")? as i32 - 1;
-        if !(0..=7).contains(&row) {
-            return Err(String::from("Row was not 1 to 8"));
-        }
+// Algebraic name of the square, e.g. e2
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.file(), self.rank())
+    }
+}
 
-        let index = (row * 8) + column;
+impl FromStr for Square {
+    type Err = MoveError;
 
-        Ok(Square::from_index(index))
+    fn from_str(name: &str) -> Result<Square, MoveError> {
+        let chars: Vec<char> = name.chars().collect();
+        if chars.is_empty() || !('a'..='h').contains(&chars[0]) {
+            return Err(MoveError::notation(name, 0, "Expected a file letter, a to h"));
+        }
+        if chars.len() < 2 || !('1'..='8').contains(&chars[1]) {
+            return Err(MoveError::notation(name, 1, "Expected a rank number, 1 to 8"));
+        }
+        if chars.len() > 2 {
+            return Err(MoveError::notation(name, 2, "Square should only be a file letter and a rank number, e.g. e2"));
+        }
+
+        Ok(Square::from_coord(&Coord {
+            row: 8 - chars[1].to_digit(10).unwrap() as i32,
+            column: chars[0] as i32 - 'a' as i32,
+        }))
     }
 }
 
@@ -132,33 +289,93 @@ pub struct Move {
     pub piece: ColourPiece,
     pub start: Square,
     pub end: Square,
+    // Piece the pawn becomes when reaching the last row, None for every other move
+    pub promotion: Option<Piece>,
 }
 
 impl Move {
-    fn inverse(&self) -> Move {
-        Move {
-            piece: self.piece,
-            start: self.end,
-            end: self.start,
+    // Whether moving this piece to the end square would require a promotion choice
+    pub fn is_promotion(piece: &ColourPiece, end: &Square) -> bool {
+        match piece {
+            ColourPiece { variant: Pawn, colour: White } => end.coord.row == 0,
+            ColourPiece { variant: Pawn, colour: Black } => end.coord.row == 7,
+            _ => false
         }
     }
 }
 
+// Coordinate notation, e.g. e2e4 or e7e8q
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.start, self.end)?;
+        if let Some(promotion) = self.promotion {
+            write!(f, "{}", promotion.to_char())?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Eq, PartialEq, Copy, Clone, Debug)]
 pub enum GameState {
     Playing,
     Checkmate(Colour),
     // Colour that has been checkmated
     Stalemate,
+    Draw(DrawReason),
 }
 
-// Board indexes will start at bottom left.
-#[derive(PartialEq, Copy, Clone, Debug)]
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum DrawReason {
+    // 50 moves by each side without a capture or pawn move
+    FiftyMoveRule,
+    ThreefoldRepetition,
+    FivefoldRepetition,
+    InsufficientMaterial,
+}
+
+impl fmt::Display for DrawReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DrawReason::FiftyMoveRule => write!(f, "fifty-move rule"),
+            DrawReason::ThreefoldRepetition => write!(f, "threefold repetition"),
+            DrawReason::FivefoldRepetition => write!(f, "fivefold repetition"),
+            DrawReason::InsufficientMaterial => write!(f, "insufficient material"),
+        }
+    }
+}
+
+// Index 0 is a8, the top left square from white's side, and index 63 is h1. So row 0 is rank 8,
+// black's back rank, column 0 is the a file, and e2 (white's king pawn) is row 6, column 4, index 52.
+#[derive(Clone, Debug)]
 pub struct Board {
-    pub pieces: [Space; 64],
+    // Kept in sync with the bitboards below, which are used for move generation
+    pieces: [Space; 64],
+    // Squares occupied by each colour
+    colours: [Bitboard; 2],
+    // Squares occupied by each piece type, per colour
+    bitboards: [[Bitboard; 6]; 2],
     pub turn: Colour,
-    can_white_castle: [Option<CastleSide>; 2],
-    can_black_castle: [Option<CastleSide>; 2],
+    castling: CastlingRights,
+    // Square skipped over by a pawn that has just moved two squares, if any
+    en_passant: Option<Square>,
+    // Half moves since the last capture or pawn move
+    halfmove_clock: u32,
+    // Starts at 1 and goes up after each move by black
+    fullmove_number: u32,
+    // Zobrist key of everything but the side to move, kept up to date as the board changes
+    key: u64,
+    // Keys of every position reached so far, including the current one
+    history: Vec<u64>,
+}
+
+// Everything make_move changes that cannot be worked out again from the move itself
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Undo {
+    captured: Option<ColourPiece>,
+    castling: CastlingRights,
+    en_passant: Option<Square>,
+    halfmove_clock: u32,
+    key: u64,
 }
 
 impl Default for Board {
@@ -169,97 +386,271 @@ impl Default for Board {
 
 impl Board {
     pub fn new() -> Board {
-        Board {
-            // Generated using python file
-            pieces: [Full(ColourPiece { variant: Rook, colour: Black }), Full(ColourPiece { variant: Knight, colour: Black }), Full(ColourPiece { variant: Bishop, colour: Black }), Full(ColourPiece { variant: Queen, colour: Black }), Full(ColourPiece { variant: King, colour: Black }), Full(ColourPiece { variant: Bishop, colour: Black }), Full(ColourPiece { variant: Knight, colour: Black }), Full(ColourPiece { variant: Rook, colour: Black }), Full(ColourPiece { variant: Pawn, colour: Black }), Full(ColourPiece { variant: Pawn, colour: Black }), Full(ColourPiece { variant: Pawn, colour: Black }), Full(ColourPiece { variant: Pawn, colour: Black }), Full(ColourPiece { variant: Pawn, colour: Black }), Full(ColourPiece { variant: Pawn, colour: Black }), Full(ColourPiece { variant: Pawn, colour: Black }), Full(ColourPiece { variant: Pawn, colour: Black }), Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Full(ColourPiece { variant: Pawn, colour: White }), Full(ColourPiece { variant: Pawn, colour: White }), Full(ColourPiece { variant: Pawn, colour: White }), Full(ColourPiece { variant: Pawn, colour: White }), Full(ColourPiece { variant: Pawn, colour: White }), Full(ColourPiece { variant: Pawn, colour: White }), Full(ColourPiece { variant: Pawn, colour: White }), Full(ColourPiece { variant: Pawn, colour: White }), Full(ColourPiece { variant: Rook, colour: White }), Full(ColourPiece { variant: Knight, colour: White }), Full(ColourPiece { variant: Bishop, colour: White }), Full(ColourPiece { variant: Queen, colour: White }), Full(ColourPiece { variant: King, colour: White }), Full(ColourPiece { variant: Bishop, colour: White }), Full(ColourPiece { variant: Knight, colour: White }), Full(ColourPiece { variant: Rook, colour: White }), ],
-            turn: White,
-            can_black_castle: [Some(CastleSide::Queen), Some(CastleSide::King)],
-            can_white_castle: [Some(CastleSide::Queen), Some(CastleSide::King)],
+        Board::from_fen(START_FEN).unwrap()
+    }
+
+    fn from_pieces(pieces: [Space; 64], turn: Colour, castling: CastlingRights) -> Board {
+        let mut board = Board {
+            pieces: [Empty; 64],
+            colours: [0; 2],
+            bitboards: [[0; 6]; 2],
+            turn,
+            castling,
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            key: zobrist::castling_key(castling),
+            history: Vec::new(),
+        };
+        for (index, space) in pieces.iter().enumerate() {
+            board.set_space(index as i32, *space);
         }
+        board.history.push(board.key());
+        board
     }
 
-    // No checks, this is called when checking for check, as using the move_piece function resulted
-    // in infinite recursion and a stack overflow
-    fn execute_move(&mut self, _move: Move) {
-        self.pieces[_move.end.index as usize] = Full(_move.piece);
-        self.pieces[_move.start.index as usize] = Empty;
+    pub fn pieces(&self) -> &[Space; 64] {
+        &self.pieces
+    }
 
-        // Swap rook to new position during castle
-        if _move.piece.variant == King && _move.start.coord.column == 4 && (_move.end.coord.column == 2 || _move.end.coord.column == 6) {
-            let (old_rook, new_rook) = match _move.end.coord {
-                Coord { row, column: 6 } => {
-                    (Square::from_coord(&Coord { row, column: 7 }).index, Square::from_coord(&Coord { row, column: 5 }).index)
-                }
-                Coord { row, column: 2 } => {
-                    (Square::from_coord(&Coord { row, column: 0 }).index, Square::from_coord(&Coord { row, column: 3 }).index)
-                }
-                _ => {
-                    panic!("Unexpected rook position for castle: {:?}", _move)
-                }
-            };
-            self.pieces[new_rook as usize] = self.pieces[old_rook as usize];
-            self.pieces[old_rook as usize] = Empty;
-        };
+    // The only place pieces should be changed, to keep the bitboards in sync
+    fn set_space(&mut self, index: i32, space: Space) {
+        let bit = bitboard::square_bb(index);
+
+        if let Full(old) = self.pieces[index as usize] {
+            self.colours[old.colour.index()] &= !bit;
+            self.bitboards[old.colour.index()][old.variant.index()] &= !bit;
+            self.key ^= zobrist::piece_key(old, index);
+        }
+        if let Full(new) = space {
+            self.colours[new.colour.index()] |= bit;
+            self.bitboards[new.colour.index()][new.variant.index()] |= bit;
+            self.key ^= zobrist::piece_key(new, index);
+        }
+
+        self.pieces[index as usize] = space;
     }
 
-    pub fn move_piece(&mut self, mut _move: Move) -> Result<GameState, String> {
-        if !self.validate_move(_move) { return Err("move_piece: Move was invalid...".to_string()); }
-        if self.turn != _move.piece.colour {
-            return Err(format!("It is currently {:?}'s turn!", self.turn));
+    fn colour_bb(&self, colour: Colour) -> Bitboard {
+        self.colours[colour.index()]
+    }
+
+    fn piece_bb(&self, colour: Colour, piece: Piece) -> Bitboard {
+        self.bitboards[colour.index()][piece.index()]
+    }
+
+    fn occupied(&self) -> Bitboard {
+        self.colours[0] | self.colours[1]
+    }
+
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    pub fn fullmove_number(&self) -> u32 {
+        self.fullmove_number
+    }
+
+    // How many times the current position has occurred, including now. Positions from before the
+    // last capture or pawn move can never be repeated, so they are not looked at.
+    pub fn repetition_count(&self) -> usize {
+        let key = self.key();
+        self.history.iter()
+            .rev()
+            .take(self.halfmove_clock as usize + 1)
+            .filter(|v| **v == key)
+            .count()
+    }
+
+    pub fn castling_rights(&self) -> CastlingRights {
+        self.castling
+    }
+
+    // The square a pawn can move to in order to capture en passant, if any
+    pub fn en_passant(&self) -> Option<Square> {
+        self.en_passant
+    }
+
+    // A pawn moving diagonally onto the en passant square takes the pawn beside it,
+    // rather than a piece on the end square
+    fn is_en_passant(&self, _move: Move) -> bool {
+        _move.piece.variant == Pawn && Some(_move.end) == self.en_passant && _move.start.coord.column != _move.end.coord.column
+    }
+
+    fn en_passant_capture_square(_move: Move) -> Square {
+        Square::from_coord(&Coord { row: _move.start.coord.row, column: _move.end.coord.column })
+    }
+
+    // Start and end index of the rook if the move is a castle
+    fn castle_rook_move(_move: Move) -> Option<(i32, i32)> {
+        if _move.piece.variant != King || _move.start.coord.column != 4 {
+            return None;
+        }
+
+        let row = _move.end.coord.row;
+        match _move.end.coord.column {
+            6 => Some((row * 8 + 7, row * 8 + 5)),
+            2 => Some((row * 8, row * 8 + 3)),
+            _ => None
+        }
+    }
+
+    // Plays a move without checking it is legal, returning what is needed to take it back with
+    // unmake_move. The move must at least be pseudo-legal, e.g. from get_piece_moves.
+    pub fn make_move(&mut self, _move: Move) -> Undo {
+        let mut undo = Undo {
+            captured: None,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            key: self.key,
         };
 
-        // Promotion   todo: allow for rook bishop and knight option
-        // Works because move functions dont check that
-        // the piece at start of move is the piece in move.piece
-        match _move.piece {
-            ColourPiece { variant: Pawn, colour: White } if _move.end.coord.row == 0 => {
-                _move.piece = ColourPiece {
-                    variant: Queen,
-                    colour: White,
-                }
-            }
-            ColourPiece { variant: Pawn, colour: Black } if _move.end.coord.row == 7 => {
-                _move.piece = ColourPiece {
-                    variant: Queen,
-                    colour: Black,
-                }
+        // Castling rights and the en passant square are taken out of the key here and put back
+        // in once they have changed
+        self.key ^= zobrist::castling_key(self.castling) ^ self.en_passant_key();
+
+        if self.is_en_passant(_move) {
+            let captured = Board::en_passant_capture_square(_move);
+            if let Full(piece) = self.pieces[captured.index as usize] {
+                undo.captured = Some(piece);
             }
-            _ => {}
+            self.set_space(captured.index, Empty);
+        } else if let Full(piece) = self.pieces[_move.end.index as usize] {
+            undo.captured = Some(piece);
+        }
+
+        self.update_castling_rights(_move);
+
+        // Only a double pawn push on the previous move allows en passant
+        self.en_passant = if _move.piece.variant == Pawn && (_move.start.coord.row - _move.end.coord.row).abs() == 2 {
+            Some(Square::from_coord(&Coord {
+                row: (_move.start.coord.row + _move.end.coord.row) / 2,
+                column: _move.start.coord.column,
+            }))
+        } else {
+            None
+        };
+
+        let placed = match _move.promotion {
+            Some(variant) => Full(ColourPiece { variant, colour: _move.piece.colour }),
+            None => Full(_move.piece)
+        };
+        self.set_space(_move.end.index, placed);
+        self.set_space(_move.start.index, Empty);
+
+        // Swap rook to new position during castle
+        if let Some((old_rook, new_rook)) = Board::castle_rook_move(_move) {
+            self.set_space(new_rook, self.pieces[old_rook as usize]);
+            self.set_space(old_rook, Empty);
+        }
+
+        // Switch to perspective of opposing player
+        self.turn = !self.turn;
+
+        if _move.piece.variant == Pawn || undo.captured.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if _move.piece.colour == Black {
+            self.fullmove_number += 1;
+        }
+        self.key ^= zobrist::castling_key(self.castling) ^ self.en_passant_key();
+        self.history.push(self.key());
+
+        undo
+    }
+
+    // Takes back a move made with make_move. Moves must be unmade in the reverse order they were made.
+    pub fn unmake_move(&mut self, _move: Move, undo: Undo) {
+        self.history.pop();
+        self.turn = !self.turn;
+        self.castling = undo.castling;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        if _move.piece.colour == Black {
+            self.fullmove_number -= 1;
         }
 
-        let check = self.does_move_cause_check(_move);
-        match check {
-            Some(White) => return Err("White would be in check!".to_string()),
-            Some(Black) => return Err("Black would be in check!".to_string()),
-            _ => {}
+        if let Some((old_rook, new_rook)) = Board::castle_rook_move(_move) {
+            self.set_space(old_rook, self.pieces[new_rook as usize]);
+            self.set_space(new_rook, Empty);
         }
 
-        // Disable castling upon moving a rook/king
-        if _move.piece.variant == Rook && _move.start.coord.row == 0 {
-            let side = match _move.start.coord.column {
-                0 => Some(CastleSide::Queen),
-                7 => Some(CastleSide::King),
-                _ => None
+        self.set_space(_move.end.index, Empty);
+        self.set_space(_move.start.index, Full(_move.piece));
+
+        if let Some(captured) = undo.captured {
+            let index = if self.is_en_passant(_move) {
+                Board::en_passant_capture_square(_move).index
+            } else {
+                _move.end.index
             };
-            if side.is_some() {
-                match (self.turn, side.unwrap()) {
-                    (White, CastleSide::Queen) => { self.can_white_castle[0] = None }
-                    (White, CastleSide::King) => { self.can_white_castle[1] = None }
-                    (Black, CastleSide::Queen) => { self.can_black_castle[0] = None }
-                    (Black, CastleSide::King) => { self.can_black_castle[1] = None }
+            self.set_space(index, Full(captured));
+        }
+        self.key = undo.key;
+    }
+
+    // Passes the turn to the other side without moving, for the engine to see how good a position is
+    // even if the side to move could do nothing. Repetitions are not looked for past a null move.
+    fn make_null_move(&mut self) -> Undo {
+        let undo = Undo {
+            captured: None,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            key: self.key,
+        };
+        self.key ^= self.en_passant_key();
+        self.en_passant = None;
+        self.halfmove_clock = 0;
+        self.turn = !self.turn;
+        self.history.push(self.key());
+        undo
+    }
+
+    fn unmake_null_move(&mut self, undo: Undo) {
+        self.history.pop();
+        self.turn = !self.turn;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.key = undo.key;
+    }
+
+    // Disable castling upon moving the king, or upon anything moving from or to a rook's starting
+    // square, which covers both the rook moving and the rook being captured
+    fn update_castling_rights(&mut self, _move: Move) {
+        if _move.piece.variant == King {
+            self.castling.remove_colour(_move.piece.colour);
+        }
+
+        for colour in [White, Black] {
+            for side in [CastleSide::King, CastleSide::Queen] {
+                let rook_coord = Coord { row: home_row(&colour), column: side.rook_column() };
+                if _move.start.coord == rook_coord || _move.end.coord == rook_coord {
+                    self.castling.remove(colour, side);
                 }
             }
-        } else if _move.piece.variant == King {
-            match self.turn {
-                White => self.can_white_castle = [None, None],
-                Black => self.can_black_castle = [None, None],
-            };
+        }
+    }
+
+    pub fn move_piece(&mut self, _move: Move) -> Result<GameState, MoveError> {
+        if self.pieces[_move.start.index as usize] == Empty {
+            return Err(MoveError::EmptySquare(_move.start));
+        }
+        if self.turn != _move.piece.colour {
+            return Err(MoveError::WrongTurn(self.turn));
         };
+        if !self.validate_move(_move) {
+            return Err(MoveError::CannotReach { piece: _move.piece.variant, start: Some(_move.start), end: _move.end });
+        }
 
-        self.execute_move(_move);
+        if !self.is_legal(_move) {
+            return Err(MoveError::LeavesKingInCheck(_move.piece.colour));
+        }
 
-        // Switch to perspective of opposing player
-        self.turn = !self.turn;
+        self.make_move(_move);
 
         // Check for checkmate before returning control to the player
         Ok(self.check_mate())
@@ -280,106 +671,115 @@ impl Board {
     // Must be an option, as when checking for threats, it assumes that the piece will take the king
     // resulting in a board with no king when checking.
     fn get_king(&self, colour: &Colour) -> Option<Square> {
-        self.pieces.iter().enumerate().find_map(|(index, space)| {
-            match space {
-                Empty => None,
-                Full(piece) => {
-                    if &piece.colour == colour && piece.variant == King {
-                        Some(Square::from_index(index as i32))
-                    } else { None }
-                }
-            }
-        })
+        Indexes(self.piece_bb(*colour, King)).next().map(Square::from_index)
     }
 
     fn get_possible_moves(&self, colour: &Colour, exclude_castle: bool) -> Vec<Move> {
-        self.pieces.iter()
-            .enumerate()
-            .filter_map(|(index, v)| match v {
-                Empty => None,
-                Full(piece) => {
-                    if &piece.colour == colour {
-                        Some(self.get_piece_moves(*piece, index as i32, exclude_castle))
-                    } else {
-                        None
-                    }
-                }
-            })
-            .flatten()
-            .collect()
+        let mut moves = Vec::with_capacity(64);
+        moves::colour_moves(colour, self, exclude_castle, &mut moves);
+        moves
     }
 
+    // Pawns threaten diagonally even when there is nothing there to take,
+    // and never threaten the squares in front of them
     fn is_threatened(&self, threatening_colour: &Colour, square: Square) -> bool {
-        let vec = self.get_possible_moves(threatening_colour, true);
-        vec.iter().any(|v| v.end == square)
+        moves::is_attacked(self, square.index, threatening_colour)
     }
 
-    fn in_check_state(&self) -> Option<Colour> {
-        let w_king = match self.get_king(&White) {
-            Some(square) => square,
-            None => return None
-        };
+    // Boards without a king, which only come up in tests, are never in check
+    fn is_king_threatened(&self, colour: &Colour) -> bool {
+        self.get_king(colour).is_some_and(|king| self.is_threatened(&!*colour, king))
+    }
 
-        let b_king = match self.get_king(&Black) {
-            Some(square) => square,
-            None => return None
-        };
+    // Whether the side to move is in check
+    pub fn in_check(&self) -> bool {
+        self.is_king_threatened(&self.turn)
+    }
 
-        if self.is_threatened(&Black, w_king) && self.turn == White {
-            Some(White)
-        } else if self.is_threatened(&White, b_king) && self.turn == Black {
-            Some(Black)
-        } else {
-            None
+    // Every move the side to move can make without leaving their own king in check
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let moves = self.get_possible_moves(&self.turn, false);
+        self.clone().retain_legal(moves)
+    }
+
+    // Legal moves of the piece on square. Empty if the square is empty or it is not that piece's turn.
+    pub fn legal_square_moves(&self, square: Square) -> Vec<Move> {
+        match self.pieces[square.index as usize] {
+            Full(piece) if piece.colour == self.turn => {
+                let moves = self.get_piece_moves(piece, square.index, false);
+                self.clone().retain_legal(moves)
+            }
+            _ => Vec::new()
         }
     }
 
-    fn check_mate(&self) -> GameState {
-        let moves: Vec<Move> = self.get_possible_moves(&self.turn, false)
-            .iter()
-            .filter(|v| match self.does_move_cause_check(**v) {
-                None => true,
-                Some(colour) => { colour != self.turn }
-            })
-            .copied()
-            .collect();
+    fn retain_legal(&mut self, mut moves: Vec<Move>) -> Vec<Move> {
+        moves.retain(|v| self.is_legal(*v));
+        moves
+    }
 
-        let no_moves = moves.is_empty();
+    // Assumes the move is already one of the pseudo-legal moves from get_piece_moves
+    fn is_legal(&mut self, _move: Move) -> bool {
+        let undo = self.make_move(_move);
+        let legal = !self.is_king_threatened(&_move.piece.colour);
+        self.unmake_move(_move, undo);
+        legal
+    }
+
+    fn check_mate(&mut self) -> GameState {
+        let moves = self.get_possible_moves(&self.turn, false);
+        let no_moves = !moves.into_iter().any(|v| self.is_legal(v));
 
-        if no_moves && self.in_check_state().is_some() {
+        if no_moves && self.in_check() {
             GameState::Checkmate(self.turn)
         } else if no_moves {
             GameState::Stalemate
+        } else if self.insufficient_material() {
+            GameState::Draw(DrawReason::InsufficientMaterial)
+        } else if self.repetition_count() >= 5 {
+            GameState::Draw(DrawReason::FivefoldRepetition)
+        } else if self.repetition_count() >= 3 {
+            GameState::Draw(DrawReason::ThreefoldRepetition)
+        } else if self.halfmove_clock >= 100 {
+            GameState::Draw(DrawReason::FiftyMoveRule)
         } else {
             GameState::Playing
         }
     }
 
-    fn does_move_cause_check(&self, _move: Move) -> Option<Colour> {
-        let mut new_board = *self;
-        new_board.execute_move(_move);
-        new_board.in_check_state()
-    }
+    // Neither side can ever checkmate: lone kings, a single minor piece, or only bishops that are
+    // all on the same colour of square
+    fn insufficient_material(&self) -> bool {
+        let both = |piece: Piece| self.piece_bb(White, piece) | self.piece_bb(Black, piece);
+
+        if both(Pawn) | both(Rook) | both(Queen) != 0 {
+            return false;
+        }
 
-    pub fn attempt_move_with_coords(&mut self, start: Coord, end: Coord) -> Result<GameState, String> {
-        if validate_coord(&start) && validate_coord(&end) {
-            let start = Square::from_coord(&start);
-            let end = Square::from_coord(&end);
-            let piece = self.piece_at_coord(&start.coord).ok_or_else(|| "Empty square used as start.".to_string())?;
+        let bishops = both(Bishop);
+        match both(Knight).count_ones() {
+            0 => bishops & LIGHT_SQUARES == 0 || bishops & !LIGHT_SQUARES == 0,
+            1 => bishops == 0,
+            _ => false
+        }
+    }
 
-            let new_move = Move { piece, start, end };
+    // Promotion is only used if the move takes a pawn to the last row
+    pub fn attempt_move_with_coords(&mut self, start: Coord, end: Coord, promotion: Option<Piece>) -> Result<GameState, MoveError> {
+        let start = Square::try_from(start)?;
+        let end = Square::try_from(end)?;
+        let piece = self.piece_at_coord(&start.coord).ok_or(MoveError::EmptySquare(start))?;
 
-            if self.validate_move(new_move) {
-                self.move_piece(new_move)
-            } else {
-                Err("attempt_move: Move was invalid".to_string())
-            }
+        let promotion = if Move::is_promotion(&piece, &end) {
+            Some(promotion.ok_or(MoveError::PromotionRequired)?)
         } else {
-            Err("attempt_move: Invalid coords passed".to_string())
-        }
+            None
+        };
+
+        self.move_piece(Move { piece, start, end, promotion })
     }
 
-    fn locate_from_target_move(&self, piece: &ColourPiece, desired_square: Square) -> Result<Square, String> {
+    fn locate_from_target_move(&self, piece: &ColourPiece, desired_square: Square) -> Result<Square, MoveError> {
         let piece_matches: Vec<Square> = self.pieces
             .iter()
             .enumerate()
@@ -387,7 +787,7 @@ impl Board {
                 if let Full(cpiece) = value {
                     cpiece.variant == piece.variant
                         && cpiece.colour == piece.colour
-                        && self.get_piece_moves(*cpiece, (*index) as i32, false)
+                        && self.legal_square_moves(Square::from_index(*index as i32))
                         .iter()
                         .map(|v| v.end)
                         .any(|v| v == desired_square)
@@ -399,44 +799,30 @@ impl Board {
             .collect();
 
         match piece_matches.len() {
-            0 => Err("No pieces can make that move.".to_string()),
+            0 => Err(MoveError::CannotReach { piece: piece.variant, start: None, end: desired_square }),
             1 => Ok(piece_matches[0]),
-            _ => Err("Try choosing a piece with a specific coordinate. E.g. rb3b5".to_string()),
+            _ => Err(MoveError::Ambiguous(piece_matches)),
         }
     }
 
+    // Pseudo-legal moves for a piece on the given index, which may leave its own king in check.
+    // Use legal_moves or legal_square_moves for the moves that can actually be played.
     pub fn get_piece_moves(&self, piece: ColourPiece, index: i32, exclude_castle: bool) -> Vec<Move> {
-        // todo: only return moves that would not cause check
         if !(0..=63).contains(&index) {
             panic!("Index given was: {}, when max is 63.", index)
         }
-        let coord = Square::from_index(index).coord;
-
-        let potential_coords: Vec<Coord> = match piece.variant {
-            King => moves::piece_moveset(piece, self, &coord, exclude_castle),
-            _ => moves::piece_moveset(piece, self, &coord, false)
-        };
 
-        potential_coords.into_iter()
-            .filter(|v| {
-                if validate_coord(v) {
-                    *v != coord && match self.piece_at_coord(v) {
-                        Some(colour_piece) => colour_piece.colour != piece.colour,
-                        None => true,
-                    }
-                } else {
-                    false
-                }
-            })
-            .map(|v| Move {
-                piece,
-                start: Square::from_coord(&coord),
-                end: Square::from_coord(&v),
-            })
-            .collect()
+        let mut moves = Vec::new();
+        moves::piece_moves(piece, self, index, exclude_castle, &mut moves);
+        moves
     }
 
+    // Whether the move is pseudo-legal, ignoring whose turn it is and whether the king is left in check
     fn validate_move(&self, _move: Move) -> bool {
+        if self.pieces[_move.start.index as usize] != Full(_move.piece) {
+            return false;
+        }
+
         let valid_moves = self.get_piece_moves(_move.piece, _move.start.index, false);
 
         valid_moves.contains(&_move)
@@ -445,71 +831,45 @@ impl Board {
     pub fn can_square_move(&self, space: &Space, square: &Square) -> bool {
         match space {
             Empty => false,
-            Full(piece) => !self.get_piece_moves(*piece, square.index, false).is_empty() && piece.colour == self.turn
+            Full(piece) => piece.colour == self.turn && !self.legal_square_moves(*square).is_empty()
         }
     }
 
     fn can_castle(&self, colour: &Colour, side: CastleSide) -> bool {
-        match colour {
-            White => {
-                if !self.can_white_castle.contains(&Some(side)) {
-                    return false;
-                }
-            }
-            Black => {
-                if !self.can_black_castle.contains(&Some(side)) {
-                    return false;
-                }
-            }
+        if !self.castling.has(*colour, side) {
+            return false;
         }
 
-        // Last square of check squares is new position of rook
-        // Second last square of check squares is new position of king
-        let check_squares = match colour {
-            Black => {
-                match side {
-                    CastleSide::King => {
-                        vec![
-                            Square::from_index(5),
-                            Square::from_index(6),
-                        ]
-                    }
-                    CastleSide::Queen => {
-                        vec![
-                            Square::from_index(1),
-                            Square::from_index(2),
-                            Square::from_index(3),
-                        ]
-                    }
-                }
-            }
-            White => {
-                match side {
-                    CastleSide::King => {
-                        vec![
-                            Square::from_coord(&Coord { row: 7, column: 6 }),
-                            Square::from_coord(&Coord { row: 7, column: 5 }),
-                        ]
-                    }
-                    CastleSide::Queen => {
-                        vec![
-                            Square::from_coord(&Coord { row: 7, column: 1 }),
-                            Square::from_coord(&Coord { row: 7, column: 2 }),
-                            Square::from_coord(&Coord { row: 7, column: 3 }),
-                        ]
-                    }
-                }
-            }
+        let row = home_row(colour);
+        let king = Square::from_coord(&Coord { row, column: 4 });
+
+        // Rights should already be gone if either piece has moved, but make sure they are really there
+        if self.piece_at_coord(&king.coord) != Some(ColourPiece { variant: King, colour: *colour })
+            || self.piece_at_coord(&Coord { row, column: side.rook_column() }) != Some(ColourPiece { variant: Rook, colour: *colour }) {
+            return false;
+        }
+
+        // Squares between the king and rook must be empty, and the squares the king passes over
+        // or lands on must not be threatened
+        let (between, king_path) = match side {
+            CastleSide::King => (vec![5, 6], vec![5, 6]),
+            CastleSide::Queen => (vec![1, 2, 3], vec![3, 2]),
         };
 
-        // Make sure none of the squares are threatened
-        !check_squares.iter().any(|v| {
-            self.is_threatened(&(!*colour), *v) || matches!(self.pieces[v.index as usize], Full(_piece))
-        })
+        if between.iter().any(|column| self.piece_at_coord(&Coord { row, column: *column }).is_some()) {
+            return false;
+        }
+
+        // Cannot castle out of check
+        !self.is_threatened(&!*colour, king)
+            && !king_path.iter().any(|column| {
+                self.is_threatened(&!*colour, Square::from_coord(&Coord { row, column: *column }))
+            })
     }
 
+    // Legal moves of the piece on square, or None if the square is empty
     pub fn get_square_moves(&self, square: Square) -> Option<Vec<Move>> {
-        self.piece_at_coord(&square.coord).map(|piece| self.get_piece_moves(piece, square.index, false))
+        self.piece_at_coord(&square.coord).map(|_| self.legal_square_moves(square))
     }
 }
 
@@ -518,17 +878,27 @@ fn validate_coord(coord: &Coord) -> bool {
 }
 
 
-pub fn parse_str_move(move_string: &str, board: &Board) -> Result<Move, String> {
-    if !(move_string.is_ascii()) {
-        return Err(String::from("String passed was not ascii"));
+
+pub fn parse_str_move(move_string: &str, board: &Board) -> Result<Move, MoveError> {
+    if let Some(position) = move_string.chars().position(|v| !v.is_ascii()) {
+        return Err(MoveError::notation(move_string, position, "Only ascii characters can be used"));
     };
 
-    let char_vec: Vec<char> = move_string.chars().collect();
+    let mut char_vec: Vec<char> = move_string.chars().collect();
+
+    // An optional trailing piece letter chooses what a pawn is promoted to, e.g. pe8n or pe7e8n
+    let promotion = match char_vec.len() {
+        4 | 6 => {
+            let char = char_vec.pop().unwrap();
+            Some(Piece::promotion_from_char(char).ok_or_else(|| MoveError::notation(move_string, char_vec.len(), "Invalid promotion piece"))?)
+        }
+        _ => None
+    };
 
     match char_vec.len() {
         3 => {
-            let piece_type = ColourPiece::from_char(char_vec[0], board).ok_or_else(|| String::from("Invalid piece type"))?;
-            let end_square = Square::from_str(&char_vec[1..3])?;
+            let piece_type = ColourPiece::from_char(char_vec[0], board).ok_or_else(|| MoveError::notation(move_string, 0, "Invalid piece letter"))?;
+            let end_square = parse_square_at(move_string, 1)?;
 
             let start_square = board.locate_from_target_move(&piece_type, end_square)?;
 
@@ -538,11 +908,12 @@ pub fn parse_str_move(move_string: &str, board: &Board) -> Result<Move, String>
                     piece: actual_piece,
                     start: start_square,
                     end: end_square,
+                    promotion: promotion_for(&actual_piece, &end_square, promotion)?,
                 };
                 if board.validate_move(new_move) {
                     Ok(new_move)
                 } else {
-                    Err("parse_str_move: Move was invalid".to_string())
+                    Err(MoveError::CannotReach { piece: actual_piece.variant, start: Some(start_square), end: end_square })
                 }
             } else {
                 panic!("Start square did not have a valid piece on it?")
@@ -569,36 +940,71 @@ pub fn parse_str_move(move_string: &str, board: &Board) -> Result<Move, String>
         //     }
         // }
         5 => {
-            let start_square = Square::from_str(&char_vec[1..3])?;
-            let end_square = Square::from_str(&char_vec[3..5])?;
+            let start_square = parse_square_at(move_string, 1)?;
+            let end_square = parse_square_at(move_string, 3)?;
 
             if let Full(actual_piece) = board.pieces[start_square.index as usize] {
                 let new_move = Move {
                     piece: actual_piece,
                     start: start_square,
                     end: end_square,
+                    promotion: promotion_for(&actual_piece, &end_square, promotion)?,
                 };
                 if board.validate_move(new_move) {
                     Ok(new_move)
                 } else {
-                    Err("parse_str: Move was invalid".to_string())
+                    Err(MoveError::CannotReach { piece: actual_piece.variant, start: Some(start_square), end: end_square })
                 }
             } else {
-                Err("Start square had no piece on it".to_string())
+                Err(MoveError::EmptySquare(start_square))
             }
         }
 
-        any_length => Err(format!("Invalid length passed: {}", any_length))
+        // Point at where another character was expected, or at the first one too many
+        any_length => Err(MoveError::notation(move_string, any_length.min(6), "Moves should be like nf3, ng1f3 or pe8q"))
+    }
+}
+
+// Reads the two character square starting at position, with errors pointing into the whole notation
+fn parse_square_at(notation: &str, position: usize) -> Result<Square, MoveError> {
+    let name: String = notation.chars().skip(position).take(2).collect();
+    name.parse::<Square>().map_err(|err| match err {
+        MoveError::Notation { position: offset, reason, .. } => MoveError::notation(notation, position + offset, &reason),
+        err => err
+    })
+}
+
+// Promotions default to a queen when no piece is given
+fn promotion_for(piece: &ColourPiece, end: &Square, promotion: Option<Piece>) -> Result<Option<Piece>, MoveError> {
+    if Move::is_promotion(piece, end) {
+        Ok(Some(promotion.unwrap_or(Queen)))
+    } else if promotion.is_some() {
+        Err(MoveError::InvalidPromotion)
+    } else {
+        Ok(None)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{Black, Board, Square, White};
+    use crate::{parse_str_move, MoveError, Bishop, Black, Board, CastleSide, CastlingRights, ColourPiece, Coord, DrawReason, Empty, Full, GameState, King, Knight, Pawn, Queen, Rook, Square, White};
+
+    // Each move is [start row, start column, end row, end column]
+    fn play(board: &mut Board, moves: &[[i32; 4]]) -> Result<GameState, MoveError> {
+        let mut state = GameState::Playing;
+        for [start_row, start_column, end_row, end_column] in moves {
+            state = board.attempt_move_with_coords(
+                Coord { row: *start_row, column: *start_column },
+                Coord { row: *end_row, column: *end_column },
+                None,
+            )?;
+        }
+        Ok(state)
+    }
 
     #[test]
     fn board() {
-        let board = Board::new();
+        let _board = Board::new();
     }
 
     #[test]
@@ -620,6 +1026,31 @@ mod tests {
         assert_eq!(indexed_square4.column, 3)
     }
 
+    #[test]
+    fn square_names() {
+        let e2: Square = "e2".parse().unwrap();
+        assert_eq!(e2.index, 52);
+        assert_eq!((e2.file(), e2.rank()), ('e', 2));
+        assert_eq!(e2.to_string(), "e2");
+        assert_eq!(Board::new().pieces()[e2.index as usize], Full(ColourPiece { variant: Pawn, colour: White }));
+
+        assert_eq!(Square::from_index(0).to_string(), "a8");
+        assert_eq!(Square::from_index(63).to_string(), "h1");
+        for index in 0..64 {
+            let square = Square::from_index(index);
+            assert_eq!(square.to_string().parse(), Ok(square));
+        }
+
+        assert!("e9".parse::<Square>().is_err());
+        assert!("52".parse::<Square>().is_err());
+        assert!("e".parse::<Square>().is_err());
+
+        assert_eq!(Square::try_from(Coord { row: 6, column: 4 }), Ok(e2));
+        assert!(Square::try_from(Coord { row: 8, column: 0 }).is_err());
+        assert_eq!(Square::try_from(64), Err(MoveError::IndexOffBoard(64)));
+        assert_eq!(Square::try_from(-1), Err(MoveError::IndexOffBoard(-1)));
+    }
+
     #[test]
     fn colour() {
         assert_eq!(White, !Black);
@@ -628,4 +1059,262 @@ mod tests {
         assert_eq!(Black, Black);
         assert_ne!(Black, White);
     }
+
+    #[test]
+    fn en_passant() {
+        let mut board = Board::new();
+        // e4 a6 e5 d5
+        play(&mut board, &[[6, 4, 4, 4], [1, 0, 2, 0], [4, 4, 3, 4], [1, 3, 3, 3]]).unwrap();
+        assert_eq!(board.en_passant(), Some(Square::from_coord(&Coord { row: 2, column: 3 })));
+
+        // exd6
+        play(&mut board, &[[3, 4, 2, 3]]).unwrap();
+        assert_eq!(board.piece_at_coord(&Coord { row: 3, column: 3 }), None);
+        assert_eq!(board.piece_at_coord(&Coord { row: 2, column: 3 }).map(|v| (v.variant, v.colour)), Some((Pawn, White)));
+        assert_eq!(board.en_passant(), None);
+    }
+
+    #[test]
+    fn en_passant_expires() {
+        let mut board = Board::new();
+        // e4 a6 e5 d5 h3 h6
+        play(&mut board, &[[6, 4, 4, 4], [1, 0, 2, 0], [4, 4, 3, 4], [1, 3, 3, 3], [6, 7, 5, 7], [1, 7, 2, 7]]).unwrap();
+        assert_eq!(board.en_passant(), None);
+        assert!(play(&mut board, &[[3, 4, 2, 3]]).is_err());
+    }
+
+    fn empty_board() -> Board {
+        Board::from_pieces([Empty; 64], White, CastlingRights::none())
+    }
+
+    #[test]
+    fn en_passant_exposing_king() {
+        let mut board = empty_board();
+        board.turn = Black;
+        board.set_space(24, Full(ColourPiece { variant: King, colour: White }));
+        board.set_space(25, Full(ColourPiece { variant: Pawn, colour: White }));
+        board.set_space(10, Full(ColourPiece { variant: Pawn, colour: Black }));
+        board.set_space(31, Full(ColourPiece { variant: Rook, colour: Black }));
+        board.set_space(7, Full(ColourPiece { variant: King, colour: Black }));
+
+        // c5, then bxc6 would leave the rook on h5 attacking the king on a5
+        play(&mut board, &[[1, 2, 3, 2]]).unwrap();
+        assert!(play(&mut board, &[[3, 1, 2, 2]]).is_err());
+        assert!(board.piece_at_coord(&Coord { row: 3, column: 2 }).is_some());
+    }
+
+    #[test]
+    fn under_promotion() {
+        let mut board = empty_board();
+        board.set_space(60, Full(ColourPiece { variant: King, colour: White }));
+        board.set_space(10, Full(ColourPiece { variant: Pawn, colour: White }));
+        board.set_space(0, Full(ColourPiece { variant: King, colour: Black }));
+
+        let promotions = board.get_square_moves(Square::from_index(10)).unwrap();
+        assert_eq!(promotions.len(), 4);
+
+        // Pawn must be told what to promote to when moved by coordinates
+        assert!(board.attempt_move_with_coords(Coord { row: 1, column: 2 }, Coord { row: 0, column: 2 }, None).is_err());
+
+        let _move = parse_str_move("pc8n", &board).unwrap();
+        assert_eq!(_move.promotion, Some(Knight));
+        board.move_piece(_move).unwrap();
+        assert_eq!(board.piece_at_coord(&Coord { row: 0, column: 2 }), Some(ColourPiece { variant: Knight, colour: White }));
+    }
+
+    #[test]
+    fn promotion_defaults_to_queen() {
+        let mut board = empty_board();
+        board.set_space(60, Full(ColourPiece { variant: King, colour: White }));
+        board.set_space(10, Full(ColourPiece { variant: Pawn, colour: White }));
+        board.set_space(0, Full(ColourPiece { variant: King, colour: Black }));
+
+        assert_eq!(parse_str_move("pc8", &board).unwrap().promotion, Some(Queen));
+        assert!(parse_str_move("kd1q", &board).is_err());
+    }
+
+    // Kings and rooks on their starting squares with all castling rights
+    fn castling_board() -> Board {
+        let mut board = Board::from_pieces([Empty; 64], White, CastlingRights::all());
+        board.set_space(4, Full(ColourPiece { variant: King, colour: Black }));
+        board.set_space(0, Full(ColourPiece { variant: Rook, colour: Black }));
+        board.set_space(7, Full(ColourPiece { variant: Rook, colour: Black }));
+        board.set_space(60, Full(ColourPiece { variant: King, colour: White }));
+        board.set_space(56, Full(ColourPiece { variant: Rook, colour: White }));
+        board.set_space(63, Full(ColourPiece { variant: Rook, colour: White }));
+        board
+    }
+
+    fn castle_targets(board: &Board, index: i32) -> Vec<i32> {
+        board.get_square_moves(Square::from_index(index)).unwrap()
+            .into_iter()
+            .map(|v| v.end.coord.column)
+            .filter(|column| *column == 2 || *column == 6)
+            .collect()
+    }
+
+    #[test]
+    fn castling_rights_lost_on_rook_move() {
+        let mut board = castling_board();
+        play(&mut board, &[[7, 7, 6, 7]]).unwrap();
+        assert!(!board.castling_rights().has(White, CastleSide::King));
+        assert!(board.castling_rights().has(White, CastleSide::Queen));
+
+        play(&mut board, &[[0, 0, 1, 0]]).unwrap();
+        assert!(!board.castling_rights().has(Black, CastleSide::Queen));
+        assert!(board.castling_rights().has(Black, CastleSide::King));
+    }
+
+    #[test]
+    fn castling_rights_lost_on_rook_capture() {
+        let mut board = castling_board();
+        // Rook takes rook on h8, removing both colours' king side rights
+        play(&mut board, &[[7, 7, 0, 7]]).unwrap();
+        assert!(!board.castling_rights().has(White, CastleSide::King));
+        assert!(!board.castling_rights().has(Black, CastleSide::King));
+        assert!(board.castling_rights().has(Black, CastleSide::Queen));
+    }
+
+    #[test]
+    fn castling_rights_lost_on_king_move() {
+        let mut board = castling_board();
+        play(&mut board, &[[7, 4, 6, 4]]).unwrap();
+        assert_eq!(board.castling_rights(), CastlingRights { black_king_side: true, black_queen_side: true, ..CastlingRights::none() });
+    }
+
+    #[test]
+    fn castling_legality() {
+        let mut board = castling_board();
+        assert_eq!(castle_targets(&board, 60), vec![2, 6]);
+
+        // Cannot castle out of check
+        board.set_space(36, Full(ColourPiece { variant: Rook, colour: Black }));
+        assert!(castle_targets(&board, 60).is_empty());
+
+        // Queen side is still allowed when only the b file square is attacked
+        board.set_space(36, Empty);
+        board.set_space(33, Full(ColourPiece { variant: Rook, colour: Black }));
+        assert_eq!(castle_targets(&board, 60), vec![2, 6]);
+
+        // Cannot castle through an attacked square
+        board.set_space(33, Empty);
+        board.set_space(37, Full(ColourPiece { variant: Rook, colour: Black }));
+        assert_eq!(castle_targets(&board, 60), vec![2]);
+
+        // Cannot castle with a missing rook, even with the right still present
+        board.set_space(37, Empty);
+        board.set_space(56, Full(ColourPiece { variant: Bishop, colour: White }));
+        assert_eq!(castle_targets(&board, 60), vec![6]);
+    }
+
+    #[test]
+    fn castling_moves_rook() {
+        let mut board = castling_board();
+        play(&mut board, &[[7, 4, 7, 6]]).unwrap();
+        assert_eq!(board.piece_at_coord(&Coord { row: 7, column: 5 }), Some(ColourPiece { variant: Rook, colour: White }));
+        assert_eq!(board.piece_at_coord(&Coord { row: 7, column: 7 }), None);
+    }
+
+    #[test]
+    fn repetition() {
+        let mut board = Board::new();
+        // Nf3 Nf6 Ng1 Ng8, returning to the start position
+        let shuffle = [[7, 6, 5, 5], [0, 6, 2, 5], [5, 5, 7, 6], [2, 5, 0, 6]];
+
+        assert_eq!(play(&mut board, &shuffle).unwrap(), GameState::Playing);
+        assert_eq!(board.repetition_count(), 2);
+        assert_eq!(play(&mut board, &shuffle).unwrap(), GameState::Draw(DrawReason::ThreefoldRepetition));
+        play(&mut board, &shuffle).unwrap();
+        assert_eq!(play(&mut board, &shuffle).unwrap(), GameState::Draw(DrawReason::FivefoldRepetition));
+    }
+
+    #[test]
+    fn fifty_move_rule() {
+        let mut board = castling_board();
+        board.halfmove_clock = 98;
+        assert_eq!(play(&mut board, &[[7, 0, 6, 0]]).unwrap(), GameState::Playing);
+        assert_eq!(play(&mut board, &[[0, 0, 1, 0]]).unwrap(), GameState::Draw(DrawReason::FiftyMoveRule));
+
+        // Pawn moves reset the clock
+        let mut board = Board::new();
+        play(&mut board, &[[7, 6, 5, 5], [1, 4, 3, 4]]).unwrap();
+        assert_eq!(board.halfmove_clock(), 0);
+    }
+
+    #[test]
+    fn insufficient_material() {
+        let mut board = empty_board();
+        board.set_space(60, Full(ColourPiece { variant: King, colour: White }));
+        board.set_space(0, Full(ColourPiece { variant: King, colour: Black }));
+        board.set_space(58, Full(ColourPiece { variant: Bishop, colour: White }));
+        board.set_space(2, Full(ColourPiece { variant: Bishop, colour: Black }));
+
+        // Bishops on opposite colours can still mate
+        assert_eq!(play(&mut board, &[[7, 4, 7, 3]]).unwrap(), GameState::Playing);
+
+        // Bishops that are all on the same colour cannot
+        board.set_space(2, Empty);
+        board.set_space(5, Full(ColourPiece { variant: Bishop, colour: Black }));
+        assert_eq!(play(&mut board, &[[0, 0, 1, 0]]).unwrap(), GameState::Draw(DrawReason::InsufficientMaterial));
+
+        let mut board = empty_board();
+        board.set_space(60, Full(ColourPiece { variant: King, colour: White }));
+        board.set_space(0, Full(ColourPiece { variant: King, colour: Black }));
+        board.set_space(9, Full(ColourPiece { variant: Knight, colour: White }));
+        board.set_space(1, Full(ColourPiece { variant: Knight, colour: Black }));
+        assert_eq!(play(&mut board, &[[7, 4, 7, 3]]).unwrap(), GameState::Playing);
+        // King takes knight, leaving king and knight against king
+        assert_eq!(play(&mut board, &[[0, 0, 1, 1]]).unwrap(), GameState::Draw(DrawReason::InsufficientMaterial));
+    }
+
+    #[test]
+    fn legal_moves() {
+        let board = Board::new();
+        assert_eq!(board.legal_moves().len(), 20);
+        // Not black's turn
+        assert!(board.legal_square_moves(Square::from_index(8)).is_empty());
+
+        // A piece pinned to its king has no legal moves, and is not highlighted as movable
+        let mut board = empty_board();
+        board.set_space(60, Full(ColourPiece { variant: King, colour: White }));
+        board.set_space(52, Full(ColourPiece { variant: Knight, colour: White }));
+        board.set_space(4, Full(ColourPiece { variant: Rook, colour: Black }));
+        board.set_space(0, Full(ColourPiece { variant: King, colour: Black }));
+
+        assert!(!board.get_piece_moves(ColourPiece { variant: Knight, colour: White }, 52, false).is_empty());
+        assert_eq!(board.get_square_moves(Square::from_index(52)), Some(Vec::new()));
+        assert!(!board.can_square_move(&board.pieces()[52], &Square::from_index(52)));
+        assert!(board.legal_moves().iter().all(|v| v.piece.variant == King));
+    }
+
+    // Making and then unmaking every move should leave the board exactly as it was
+    fn assert_make_unmake(board: &mut Board) {
+        for _move in board.get_possible_moves(&board.turn, false) {
+            let before = board.clone();
+            let undo = board.make_move(_move);
+            board.unmake_move(_move, undo);
+            // Boards only compare equal by position, so everything else is checked separately
+            assert_eq!(*board, before, "{:?}", _move);
+            assert_eq!(board.to_fen(), before.to_fen(), "{:?}", _move);
+            assert_eq!((board.colours, board.bitboards), (before.colours, before.bitboards), "{:?}", _move);
+            assert_eq!((board.key, &board.history), (before.key, &before.history), "{:?}", _move);
+        }
+    }
+
+    #[test]
+    fn make_unmake() {
+        let mut board = Board::new();
+        assert_make_unmake(&mut board);
+
+        // Position with en passant available, part way to the fifty move rule
+        let mut board = Board::from_fen("rnbqkbnr/1pp1pppp/p7/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3").unwrap();
+        assert_make_unmake(&mut board);
+        let mut board = Board::from_fen("4k3/8/8/8/5pP1/8/8/4K2R b K g3 12 40").unwrap();
+        assert_make_unmake(&mut board);
+
+        // Castling, captures of castling rooks, and under-promotions
+        let mut board = castling_board();
+        board.set_space(9, Full(ColourPiece { variant: Pawn, colour: White }));
+        assert_make_unmake(&mut board);
+    }
 }
@@ -2,9 +2,136 @@
 
 mod interface_terminal;
 mod interface_gui;
+mod interface_match;
+mod interface_uci;
+mod interface_xboard;
+
+use std::time::Instant;
+use chess::Board;
+use chess::engine::{Engine, Limits, Player, SearchOptions, UciEngine};
 
 
 fn main() {
-    interface_gui::launch_gui()
-    // interface_terminal::start_terminal();
+    let args: Vec<String> = std::env::args().collect();
+
+    match args.get(1).map(|v| v.as_str()) {
+        Some("terminal") => interface_terminal::start_terminal(opponent(&args), text_mode(&args)),
+        Some("perft") => run_perft(&args[2..]),
+        Some("bench") => run_bench(&args[2..]),
+        Some("uci") => interface_uci::start_uci(),
+        Some("xboard") => interface_xboard::start_xboard(),
+        Some("match") => interface_match::run_match(&args[2..]),
+        _ => interface_gui::launch_gui(opponent(&args))
+    }
+}
+
+// The engine to play against in the terminal or GUI. Usage: chess [terminal [--text]]
+// [--engine <path> [args...]] to play another engine that speaks UCI, or this crate's engine otherwise.
+fn opponent(args: &[String]) -> Box<dyn Player> {
+    let index = match args.iter().position(|v| v == "--engine") {
+        Some(index) => index,
+        None => return Box::new(Engine::new())
+    };
+    let path = match args.get(index + 1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: chess [terminal [--text]] [--engine <path> [args...]]");
+            std::process::exit(1);
+        }
+    };
+    match UciEngine::start(path, &args[index + 2..]) {
+        Ok(engine) => Box::new(engine),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Usage: chess terminal --text [--engine <path> [args...]] to type moves at a prompt rather than
+// clicking on the board
+fn text_mode(args: &[String]) -> bool {
+    let engine_args = args.iter().position(|v| v == "--engine").unwrap_or(args.len());
+    args[..engine_args].iter().any(|v| v == "--text")
+}
+
+// Usage: chess perft <depth> [fen], using the starting position if no FEN is given
+fn run_perft(args: &[String]) {
+    let depth: u32 = match args.first().map(|v| v.parse()) {
+        Some(Ok(depth)) => depth,
+        _ => {
+            eprintln!("Usage: chess perft <depth> [fen]");
+            return;
+        }
+    };
+
+    let board = if args.len() > 1 {
+        Board::from_fen(&args[1..].join(" "))
+    } else {
+        Ok(Board::new())
+    };
+    let mut board = match board {
+        Ok(board) => board,
+        Err(err) => {
+            eprintln!("{}", err);
+            return;
+        }
+    };
+
+    let start = Instant::now();
+    let divide = board.perft_divide(depth);
+    let elapsed = start.elapsed();
+
+    for (_move, nodes) in &divide {
+        println!("{}: {}", _move, nodes);
+    }
+    // Nothing to split up at depth 0, where the only node is the position itself
+    let total: u64 = match depth {
+        0 => board.perft(0),
+        _ => divide.iter().map(|(_, nodes)| nodes).sum(),
+    };
+    println!("\nNodes searched: {}", total);
+    println!("Time: {:.3}s ({:.0} nodes/s)", elapsed.as_secs_f64(), total as f64 / elapsed.as_secs_f64());
+}
+
+// Usage: chess bench [depth], searching a few positions with each part of the search switched off
+// in turn, to show how many nodes each of them saves
+fn run_bench(args: &[String]) {
+    let depth: u32 = match args.first().map(|v| v.parse()) {
+        Some(Ok(depth)) => depth,
+        None => 6,
+        Some(Err(_)) => {
+            eprintln!("Usage: chess bench [depth]");
+            return;
+        }
+    };
+    let positions = [
+        chess::START_FEN,
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 8",
+    ];
+
+    let all = SearchOptions::default();
+    let runs = [
+        ("all", all),
+        ("no null move", SearchOptions { null_move: false, ..all }),
+        ("no late move reductions", SearchOptions { late_move_reductions: false, ..all }),
+        ("no futility", SearchOptions { futility: false, ..all }),
+        ("no reverse futility", SearchOptions { reverse_futility: false, ..all }),
+        ("no aspiration windows", SearchOptions { aspiration_windows: false, ..all }),
+        ("no check extensions", SearchOptions { check_extensions: false, ..all }),
+        ("none", SearchOptions::none()),
+    ];
+
+    for (name, options) in runs {
+        let start = Instant::now();
+        let mut nodes = 0;
+        for fen in positions {
+            let mut engine = Engine::new();
+            engine.set_options(options);
+            nodes += engine.search(&Board::from_fen(fen).unwrap(), Limits::depth(depth)).nodes;
+        }
+        println!("{:<24} {:>12} nodes {:>8.3}s", name, nodes, start.elapsed().as_secs_f64());
+    }
 }
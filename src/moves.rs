@@ -1,202 +1,123 @@
 use crate::*;
-
-pub fn piece_moveset(piece: ColourPiece, board: &Board, coord: &Coord, exclude_castle: bool) -> Vec<Coord> {
-    match piece {
-        ColourPiece { variant: Pawn, colour } => pawn_moves(&colour, coord, board),
-
-        ColourPiece { variant: Knight, colour } => knight_moves(coord, &colour, board),
-
-        ColourPiece { variant: Bishop, colour } => bishop_moves(coord, &colour, board),
-
-        ColourPiece { variant: Rook, colour } => rook_moves(coord, &colour, board),
-
-        ColourPiece { variant: Queen, colour } =>
-            bishop_moves(coord, &colour, board)
-                .into_iter()
-                .chain(
-                    rook_moves(coord, &colour, board).into_iter()
-                ).collect(),
-
-        ColourPiece { variant: King, colour } => king_moves(coord, &colour, board, exclude_castle)
+use crate::bitboard::*;
+
+// Adds the pseudo-legal moves for a piece on index. These may leave the piece's own king in check.
+pub fn piece_moves(piece: ColourPiece, board: &Board, index: i32, exclude_castle: bool, moves: &mut Vec<Move>) {
+    let own = board.colour_bb(piece.colour);
+    let occupied = board.occupied();
+
+    let targets = match piece.variant {
+        Pawn => pawn_targets(&piece.colour, index, board),
+        Knight => KNIGHT_ATTACKS[index as usize],
+        Bishop => bishop_attacks(index, occupied),
+        Rook => rook_attacks(index, occupied),
+        Queen => queen_attacks(index, occupied),
+        King => KING_ATTACKS[index as usize] | castle_targets(&piece.colour, board, exclude_castle),
+    } & !own;
+
+    let start = Square::from_index(index);
+    for end in Indexes(targets) {
+        let end = Square::from_index(end);
+
+        // Generate a separate move for each piece a pawn can be promoted to
+        if Move::is_promotion(&piece, &end) {
+            moves.extend(PROMOTION_PIECES.iter().map(|variant| Move { piece, start, end, promotion: Some(*variant) }));
+        } else {
+            moves.push(Move { piece, start, end, promotion: None });
+        }
     }
 }
 
-fn get_blocked_line(line: &Vec<Coord>, piece_colour: &Colour, board: &Board) -> Vec<Coord> {
-    let mut piece_found = false;
-    line.iter()
-        .filter(|v| validate_coord(*v))
-        .take_while(|v| {
-            match board.piece_at_coord(v) {
-                None => true,
-                Some(piece) => {
-                    // When find a piece, exclude all new pieces.
-                    // Also if piece is of same colour, exclude it, but if piece is opposite then include
-                    if piece_found { return false; };
-                    piece_found = true;
-
-                    &piece.colour != piece_colour
-                }
-            }
-        })
-        .cloned()
-        .collect()
+// Adds the pseudo-legal moves for every piece of a colour
+pub fn colour_moves(colour: &Colour, board: &Board, exclude_castle: bool, moves: &mut Vec<Move>) {
+    for index in Indexes(board.colour_bb(*colour)) {
+        if let Full(piece) = board.pieces[index as usize] {
+            piece_moves(piece, board, index, exclude_castle, moves);
+        }
+    }
 }
 
-fn pawn_moves(colour: &Colour, coord: &Coord, board: &Board) -> Vec<Coord> {
-    let row = coord.row;
-    let column = coord.column;
-
-    let (straight, diagonals) = match colour {
-        White => {
-            let one_up = Coord { row: row - 1, column };
-            let two_up = Coord { row: row - 2, column };
-            let north_east = Coord { row: row - 1, column: column + 1 };
-            let north_west = Coord { row: row - 1, column: column - 1 };
-
-            if row == 6 {
-                (vec![one_up, two_up], vec![north_east, north_west])
-            } else {
-                (vec![one_up], vec![north_east, north_west])
-            }
-        }
-        Black => {
-            let one_down = Coord { row: row + 1, column };
-            let two_down = Coord { row: row + 2, column };
-            let south_east = Coord { row: row + 1, column: column + 1 };
-            let south_west = Coord { row: row + 1, column: column - 1 };
-
-            if row == 1 {
-                (vec![one_down, two_down], vec![south_east, south_west])
-            } else {
-                (vec![one_down], vec![south_east, south_west])
-            }
-        }
-    };
+fn pawn_targets(colour: &Colour, index: i32, board: &Board) -> Bitboard {
+    let empty = !board.occupied();
+    let row = index / 8;
 
-    let opposite_colour = match colour {
-        White => Black,
-        Black => White
+    // Pawns cannot take forwards, so pushes are blocked by pieces of either colour
+    let (one_forward, start_row) = match colour {
+        White => (index - 8, 6),
+        Black => (index + 8, 1)
     };
+    let mut pushes = 0;
+    if (0..64).contains(&one_forward) && empty & square_bb(one_forward) != 0 {
+        pushes |= square_bb(one_forward);
 
-    // Block on both colours, because a pawn cannot take forwards
-    let mut straight = get_blocked_line(
-        &get_blocked_line(&straight, colour, board),
-        &opposite_colour,
-        board);
-
-    let mut diagonals: Vec<Coord> = diagonals
-        .into_iter()
-        .filter(|v| {
-            // Only allow diagonals if pieces diagonally are opposite colour
-            match board.piece_at_coord(v) {
-                Some(piece) => piece.colour != *colour,
-                None => false
-            }
-        })
-        .collect();
-
-    let mut moves: Vec<Coord> = Vec::with_capacity(4);
-    moves.append(&mut straight);
-    moves.append(&mut diagonals);
-
-    moves
-}
-
-fn knight_moves(coord: &Coord, colour: &Colour, board: &Board) -> Vec<Coord> {
-    let row = coord.row;
-    let column = coord.column;
-
-    vec![
-        Coord { row: row + 2, column: column + 1 },
-        Coord { row: row + 2, column: column - 1 },
-        Coord { row: row + 1, column: column + 2 },
-        Coord { row: row + 1, column: column - 2 },
-        Coord { row: row - 1, column: column + 2 },
-        Coord { row: row - 1, column: column - 2 },
-        Coord { row: row - 2, column: column + 1 },
-        Coord { row: row - 2, column: column - 1 },
-    ].into_iter().flat_map(|v| get_blocked_line(&vec![v], colour, board)).collect()
-}
-
-fn bishop_moves(coord: &Coord, colour: &Colour, board: &Board) -> Vec<Coord> {
-    let row = coord.row;
-    let column = coord.column;
+        let two_forward = one_forward + (one_forward - index);
+        if row == start_row && empty & square_bb(two_forward) != 0 {
+            pushes |= square_bb(two_forward);
+        }
+    }
 
-    let mut north_east: Vec<Coord> = get_blocked_line(&(1..8).map(|v| Coord { row: row + v, column: column + v }).collect(), colour, board);
-    let mut north_west: Vec<Coord> = get_blocked_line(&(1..8).map(|v| Coord { row: row + v, column: column - v }).collect(), colour, board);
-    let mut south_east: Vec<Coord> = get_blocked_line(&(1..8).map(|v| Coord { row: row - v, column: column + v }).collect(), colour, board);
-    let mut south_west: Vec<Coord> = get_blocked_line(&(1..8).map(|v| Coord { row: row - v, column: column - v }).collect(), colour, board);
+    // En passant can only be taken by the side whose opponent has just double pushed
+    let en_passant_row = match colour {
+        White => 2,
+        Black => 5
+    };
+    let en_passant = match board.en_passant() {
+        Some(square) if square.coord.row == en_passant_row => square_bb(square.index),
+        _ => 0
+    };
 
-    north_east.append(&mut north_west);
-    north_east.append(&mut south_east);
-    north_east.append(&mut south_west);
+    // Only allow diagonals if pieces diagonally are opposite colour, or if taking en passant
+    let captures = PAWN_ATTACKS[colour.index()][index as usize] & (board.colour_bb(!*colour) | en_passant);
 
-    north_east
+    pushes | captures
 }
 
-fn rook_moves(coord: &Coord, colour: &Colour, board: &Board) -> Vec<Coord> {
-    let row = coord.row;
-    let column = coord.column;
-
-    let mut right: Vec<Coord> = get_blocked_line(&(1..8).map(|v| Coord { row, column: column + v }).collect(), colour, board);
-    let mut up: Vec<Coord> = get_blocked_line(&(1..8).map(|v| Coord { row: row + v, column }).collect(), colour, board);
-    let mut down: Vec<Coord> = get_blocked_line(&(1..8).map(|v| Coord { row: row - v, column }).collect(), colour, board);
-    let mut left: Vec<Coord> = get_blocked_line(&(1..8).map(|v| Coord { row, column: column - v }).collect(), colour, board);
-
-    right.append(&mut up);
-    right.append(&mut down);
-    right.append(&mut left);
+fn castle_targets(colour: &Colour, board: &Board, exclude_castle: bool) -> Bitboard {
+    if exclude_castle {
+        return 0;
+    }
 
-    right
+    let row = home_row(colour);
+    let mut targets = 0;
+    if board.can_castle(colour, CastleSide::King) {
+        targets |= square_bb(row * 8 + 6);
+    }
+    if board.can_castle(colour, CastleSide::Queen) {
+        targets |= square_bb(row * 8 + 2);
+    }
+    targets
 }
 
-fn king_moves(coord: &Coord, colour: &Colour, board: &Board, exclude_castle: bool) -> Vec<Coord> {
-    let row = coord.row;
-    let column = coord.column;
-
-    // Copy from rook and bishop
-    let mut north_east: Vec<Coord> = get_blocked_line(&(-1..2).map(|v| Coord { row: row + v, column: column + v }).collect(), colour, board);
-    let mut north_west: Vec<Coord> = get_blocked_line(&(-1..2).map(|v| Coord { row: row + v, column: column - v }).collect(), colour, board);
-    let mut south_east: Vec<Coord> = get_blocked_line(&(-1..2).map(|v| Coord { row: row - v, column: column + v }).collect(), colour, board);
-    let mut south_west: Vec<Coord> = get_blocked_line(&(-1..2).map(|v| Coord { row: row - v, column: column - v }).collect(), colour, board);
-    north_east.append(&mut north_west);
-    north_east.append(&mut south_east);
-    north_east.append(&mut south_west);
-
-    let mut right: Vec<Coord> = get_blocked_line(&(-1..2).map(|v| Coord { row, column: column + v }).collect(), colour, board);
-    let mut up: Vec<Coord> = get_blocked_line(&(-1..2).map(|v| Coord { row: row + v, column }).collect(), colour, board);
-    let mut down: Vec<Coord> = get_blocked_line(&(-1..2).map(|v| Coord { row: row - v, column }).collect(), colour, board);
-    let mut left: Vec<Coord> = get_blocked_line(&(-1..2).map(|v| Coord { row, column: column - v }).collect(), colour, board);
-    right.append(&mut up);
-    right.append(&mut down);
-    right.append(&mut left);
-
-    north_east.append(&mut right);
-
-    if !exclude_castle {
-        let mut castles = Vec::new();
-
-        match colour {
-            White => {
-                if board.can_castle(colour, CastleSide::King) {
-                    castles.push(Coord {row: 7, column: 6})
-                }
-                if board.can_castle(colour, CastleSide::Queen) {
-                    castles.push(Coord {row: 7, column: 2})
-                }
-            }
-            Black => {
-                if board.can_castle(colour, CastleSide::King) {
-                    castles.push(Coord {row: 0, column: 6})
-                }
-                if board.can_castle(colour, CastleSide::Queen) {
-                    castles.push(Coord {row: 0, column: 2})
-                }
-            }
-        };
-
-        north_east.append(&mut castles);
-    }
+// Whether any piece of the attacking colour could capture on index
+pub fn is_attacked(board: &Board, index: i32, attacking: &Colour) -> bool {
+    let square = index as usize;
+    let occupied = board.occupied();
+    let bishops = board.piece_bb(*attacking, Bishop) | board.piece_bb(*attacking, Queen);
+    let rooks = board.piece_bb(*attacking, Rook) | board.piece_bb(*attacking, Queen);
+
+    // A pawn attacks this square if a pawn of the other colour here would attack that pawn
+    PAWN_ATTACKS[(!*attacking).index()][square] & board.piece_bb(*attacking, Pawn) != 0
+        || KNIGHT_ATTACKS[square] & board.piece_bb(*attacking, Knight) != 0
+        || KING_ATTACKS[square] & board.piece_bb(*attacking, King) != 0
+        || bishop_attacks(index, occupied) & bishops != 0
+        || rook_attacks(index, occupied) & rooks != 0
+}
 
-    north_east
+// Pieces of both colours that attack index, with the given squares counted as occupied. Taking
+// pieces out of occupied lets sliders behind them through, which is how x-rays are found.
+pub fn attackers(board: &Board, index: i32, occupied: Bitboard) -> Bitboard {
+    let square = index as usize;
+    let pieces = |piece: Piece| board.piece_bb(White, piece) | board.piece_bb(Black, piece);
+    let bishops = pieces(Bishop) | pieces(Queen);
+    let rooks = pieces(Rook) | pieces(Queen);
+
+    let pawns = PAWN_ATTACKS[Black.index()][square] & board.piece_bb(White, Pawn)
+        | PAWN_ATTACKS[White.index()][square] & board.piece_bb(Black, Pawn);
+
+    (pawns
+        | KNIGHT_ATTACKS[square] & pieces(Knight)
+        | KING_ATTACKS[square] & pieces(King)
+        | bishop_attacks(index, occupied) & bishops
+        | rook_attacks(index, occupied) & rooks)
+        & occupied
 }
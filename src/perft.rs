@@ -0,0 +1,126 @@
+use crate::*;
+
+impl Board {
+    // Counts every sequence of legal moves of the given length from this position. Comparing the
+    // counts against known values is the standard way of checking a move generator.
+    pub fn perft(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let mut nodes = 0;
+        for _move in self.get_possible_moves(&self.turn, false) {
+            let undo = self.make_move(_move);
+            if !self.is_king_threatened(&_move.piece.colour) {
+                nodes += self.perft(depth - 1);
+            }
+            self.unmake_move(_move, undo);
+        }
+        nodes
+    }
+
+    // Perft split up by the first move, to narrow down which move a wrong count comes from. Empty at
+    // depth 0, where there is no first move to split by.
+    pub fn perft_divide(&mut self, depth: u32) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+
+        self.legal_moves()
+            .into_iter()
+            .map(|_move| {
+                let undo = self.make_move(_move);
+                let nodes = self.perft(depth - 1);
+                self.unmake_move(_move, undo);
+                (_move, nodes)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Board, START_FEN};
+
+    fn assert_perft(fen: &str, depth: u32, expected: u64) {
+        let mut board = Board::from_fen(fen).unwrap();
+        assert_eq!(board.perft(depth), expected, "{} at depth {}", fen, depth);
+    }
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+    const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+    const POSITION_6: &str = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+    // Positions built to catch specific move generation mistakes, with their known deep counts
+    const EDGE_CASES: [(&str, &str, u32, u64); 14] = [
+        ("illegal en passant 1", "3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1", 6, 1134888),
+        ("illegal en passant 2", "8/8/4k3/8/2p5/8/B2P2K1/8 w - - 0 1", 6, 1015133),
+        ("en passant gives check", "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", 6, 1440467),
+        ("short castle gives check", "5k2/8/8/8/8/8/8/4K2R w K - 0 1", 6, 661072),
+        ("long castle gives check", "3k4/8/8/8/8/8/8/R3K3 w Q - 0 1", 6, 803711),
+        ("castling rights", "r3k2r/1b4bq/8/8/8/8/7B/R3K2R w KQkq - 0 1", 4, 1274206),
+        ("castling prevented", "r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1", 4, 1720476),
+        ("promote out of check", "2K2r2/4P3/8/8/8/8/8/3k4 w - - 0 1", 6, 3821001),
+        ("discovered check", "8/8/1P2K3/8/2n5/1q6/8/5k2 b - - 0 1", 5, 1004658),
+        ("promote to give check", "4k3/1P6/8/8/8/8/K7/8 w - - 0 1", 6, 217342),
+        ("under-promote to give check", "8/P1k5/K7/8/8/8/8/8 w - - 0 1", 6, 92683),
+        ("self stalemate", "K1k5/8/P7/8/8/8/8/8 w - - 0 1", 6, 2217),
+        ("stalemate and checkmate 1", "8/k1P5/8/1K6/8/8/8/8 w - - 0 1", 7, 567584),
+        ("stalemate and checkmate 2", "8/8/2k5/5q2/5n2/8/5K2/8 b - - 0 1", 4, 23527),
+    ];
+
+    #[test]
+    fn standard_positions() {
+        assert_perft(START_FEN, 1, 20);
+        assert_perft(START_FEN, 3, 8902);
+        assert_perft(KIWIPETE, 1, 48);
+        assert_perft(KIWIPETE, 2, 2039);
+        assert_perft(POSITION_3, 4, 43238);
+        assert_perft(POSITION_4, 3, 9467);
+        assert_perft(POSITION_5, 2, 1486);
+        assert_perft(POSITION_6, 2, 2079);
+    }
+
+    // Shallower versions of the edge cases, fast enough for debug builds
+    #[test]
+    fn edge_cases() {
+        let shallow: [(u32, u64); 14] = [
+            (4, 10138), (4, 10276), (4, 13931), (4, 6399), (4, 7418), (2, 1141), (2, 1494),
+            (4, 19174), (3, 5160), (4, 2661), (4, 1329), (4, 63), (5, 10857), (3, 6559),
+        ];
+        for ((_, fen, _, _), (depth, expected)) in EDGE_CASES.iter().zip(shallow) {
+            assert_perft(fen, depth, expected);
+        }
+    }
+
+    // Run with: cargo test --release -- --ignored
+    #[test]
+    #[ignore]
+    fn deep_positions() {
+        assert_perft(START_FEN, 5, 4865609);
+        assert_perft(KIWIPETE, 4, 4085603);
+        assert_perft(POSITION_3, 5, 674624);
+        assert_perft(POSITION_4, 4, 422333);
+        assert_perft(POSITION_5, 3, 62379);
+        assert_perft(POSITION_6, 3, 89890);
+        for (name, fen, depth, expected) in EDGE_CASES {
+            let mut board = Board::from_fen(fen).unwrap();
+            assert_eq!(board.perft(depth), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn divide() {
+        let mut board = Board::from_fen(KIWIPETE).unwrap();
+        let divide = board.perft_divide(2);
+        assert_eq!(divide.len(), 48);
+        assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), 2039);
+        assert!(divide.iter().any(|(_move, nodes)| _move.to_string() == "e1g1" && *nodes == 43));
+
+        // The position itself is the only node at depth 0
+        assert!(board.perft_divide(0).is_empty());
+        assert_eq!(board.perft(0), 1);
+    }
+}
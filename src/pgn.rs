@@ -0,0 +1,695 @@
+use crate::*;
+
+// The tags every PGN game should have, in the order they are written
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+// Export format lines must be shorter than 80 characters
+const MAX_LINE_LENGTH: usize = 79;
+
+// A game in Portable Game Notation. Moves are kept as the SAN text they were written with, so a
+// game can be read and written without being played through; replay checks that they are legal.
+#[derive(PartialEq, Clone, Debug)]
+pub struct PgnGame {
+    // In the order they were read, which may not include all of the seven tag roster
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<PgnMove>,
+    // 1-0, 0-1, 1/2-1/2, or * if the game is unfinished
+    pub result: String,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct PgnMove {
+    pub san: String,
+    // Numeric annotation glyphs, e.g. 1 for a good move. Written as $1, or as ! after the move.
+    pub nags: Vec<u8>,
+    // Comment before the move, only used for the first move of a line
+    pub comment_before: Option<String>,
+    pub comment: Option<String>,
+    // Alternative lines that could have been played instead of this move
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+impl PgnMove {
+    pub fn new(san: &str) -> PgnMove {
+        PgnMove {
+            san: san.to_string(),
+            nags: Vec::new(),
+            comment_before: None,
+            comment: None,
+            variations: Vec::new(),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Debug)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    Move(String),
+    VariationStart,
+    VariationEnd,
+    Result(String),
+}
+
+impl PgnGame {
+    // A game with no moves, and the seven tag roster filled in with unknown values
+    pub fn new() -> PgnGame {
+        PgnGame {
+            tags: SEVEN_TAG_ROSTER
+                .iter()
+                .map(|name| (name.to_string(), default_tag(name).to_string()))
+                .collect(),
+            moves: Vec::new(),
+            result: "*".to_string(),
+        }
+    }
+
+    // Records moves played from the start board as a game, with the FEN tag set if it was not the
+    // normal starting position and the result filled in if the game is over
+    pub fn from_moves(start: &Board, moves: &[Move]) -> Result<PgnGame, String> {
+        let mut game = PgnGame::new();
+        game.set_start(start);
+
+        let mut board = start.clone();
+        let mut state = GameState::Playing;
+        for _move in moves {
+            game.moves.push(PgnMove::new(&board.to_san(*_move)));
+            state = board.move_piece(*_move).map_err(|err| err.to_string())?;
+        }
+        game.set_result(state);
+
+        Ok(game)
+    }
+
+    // Sets the FEN tag if the game does not start from the normal starting position
+    pub fn set_start(&mut self, start: &Board) {
+        if start.to_fen() != START_FEN {
+            self.set_tag("SetUp", "1");
+            self.set_tag("FEN", &start.to_fen());
+        }
+    }
+
+    // Sets the result from the state at the end of the game
+    pub fn set_result(&mut self, state: GameState) {
+        self.result = match state {
+            GameState::Checkmate(White) => "0-1",
+            GameState::Checkmate(Black) => "1-0",
+            GameState::Stalemate | GameState::Draw(_) => "1/2-1/2",
+            GameState::Playing => "*",
+        }.to_string();
+        self.set_tag("Result", &self.result.clone());
+    }
+
+    // Reads a single game. Anything after the first game is an error.
+    pub fn parse(pgn: &str) -> Result<PgnGame, String> {
+        let mut games = PgnGame::parse_all(pgn)?;
+        match games.len() {
+            1 => Ok(games.remove(0)),
+            0 => Err("No game found".to_string()),
+            count => Err(format!("Expected 1 game, found {}", count))
+        }
+    }
+
+    // Reads every game in a file, which are normally separated by a blank line
+    pub fn parse_all(pgn: &str) -> Result<Vec<PgnGame>, String> {
+        let tokens = tokenize(pgn)?;
+        let mut games = Vec::new();
+        let mut position = 0;
+
+        while position < tokens.len() {
+            let mut tags = Vec::new();
+            while let Some(Token::Tag(name, value)) = tokens.get(position) {
+                tags.push((name.clone(), value.clone()));
+                position += 1;
+            }
+
+            let moves = parse_line(&tokens, &mut position, 0)?;
+
+            // Games should end in a result, but if not the tag has to be trusted
+            let result = match tokens.get(position) {
+                Some(Token::Result(result)) => {
+                    position += 1;
+                    result.clone()
+                }
+                _ => tags.iter()
+                    .find(|(name, _)| name == "Result")
+                    .map(|(_, value)| value.clone())
+                    .unwrap_or_else(|| "*".to_string())
+            };
+
+            games.push(PgnGame { tags, moves, result });
+        }
+
+        Ok(games)
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string()))
+        }
+    }
+
+    // The position the game starts from, which is only different from normal if there is a FEN tag
+    pub fn start_board(&self) -> Result<Board, String> {
+        match self.tag("FEN") {
+            Some(fen) => Board::from_fen(fen).map_err(|err| format!("Invalid FEN tag: {}", err)),
+            None => Ok(Board::new())
+        }
+    }
+
+    // Plays through the game, checking every move in the main line and the variations is legal, and
+    // returns the moves of the main line
+    pub fn main_line(&self) -> Result<Vec<Move>, String> {
+        self.play().map(|(_, played)| played)
+    }
+
+    // The position at the end of the main line
+    pub fn replay(&self) -> Result<Board, String> {
+        self.play().map(|(board, _)| board)
+    }
+
+    // The position at the end of the main line along with its moves
+    fn play(&self) -> Result<(Board, Vec<Move>), String> {
+        let mut board = self.start_board()?;
+        let mut played = Vec::with_capacity(self.moves.len());
+        play_line(&mut board, &self.moves, &mut played)?;
+        Ok((board, played))
+    }
+}
+
+impl Default for PgnGame {
+    fn default() -> Self {
+        PgnGame::new()
+    }
+}
+
+// Export format: the seven tag roster first, then any other tags, a blank line, and the movetext
+// wrapped to fit in 80 columns
+impl fmt::Display for PgnGame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for name in SEVEN_TAG_ROSTER {
+            let value = match name {
+                "Result" => &self.result,
+                _ => self.tag(name).unwrap_or(default_tag(name))
+            };
+            writeln!(f, "[{} \"{}\"]", name, escape_tag(value))?;
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.contains(&name.as_str()) {
+                writeln!(f, "[{} \"{}\"]", name, escape_tag(value))?;
+            }
+        }
+        writeln!(f)?;
+
+        // Move numbers carry on from the starting position
+        let (fullmove, turn) = match self.start_board() {
+            Ok(board) => (board.fullmove_number(), board.turn),
+            Err(_) => (1, White)
+        };
+        let mut words = Vec::new();
+        write_line(&mut words, &self.moves, fullmove, turn);
+        words.push(self.result.clone());
+
+        let mut line = String::new();
+        for word in words {
+            if !line.is_empty() && line.len() + 1 + word.len() > MAX_LINE_LENGTH {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&word);
+        }
+        writeln!(f, "{}", line)
+    }
+}
+
+fn default_tag(name: &str) -> &'static str {
+    match name {
+        "Date" => "????.??.??",
+        "Result" => "*",
+        _ => "?"
+    }
+}
+
+fn escape_tag(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Error for a move of a PGN game that cannot be played, numbered the way it is written, e.g.
+// Illegal move 2... Ke6: No King can move to e6
+pub(crate) fn illegal_move(board: &Board, san: &str, err: MoveError) -> String {
+    let number = match board.turn {
+        White => format!("{}.", board.fullmove_number()),
+        Black => format!("{}...", board.fullmove_number())
+    };
+    format!("Illegal move {} {}: {}", number, san, err)
+}
+
+fn play_line(board: &mut Board, line: &[PgnMove], played: &mut Vec<Move>) -> Result<(), String> {
+    for pgn_move in line {
+        // Variations replace this move, so they start from the position before it
+        for variation in &pgn_move.variations {
+            play_line(&mut board.clone(), variation, &mut Vec::new())?;
+        }
+
+        let _move = board.parse_san(&pgn_move.san).map_err(|err| illegal_move(board, &pgn_move.san, err))?;
+        board.move_piece(_move).map_err(|err| err.to_string())?;
+        played.push(_move);
+    }
+    Ok(())
+}
+
+// Adds the words of a line of moves, with move numbers before white's moves, and before black's
+// moves wherever the flow of moves has been broken by a comment or variation
+fn write_line(words: &mut Vec<String>, line: &[PgnMove], mut fullmove: u32, mut turn: Colour) {
+    let mut needs_number = true;
+
+    for pgn_move in line {
+        if let Some(comment) = &pgn_move.comment_before {
+            write_comment(words, comment);
+            needs_number = true;
+        }
+
+        match turn {
+            White => words.push(format!("{}.", fullmove)),
+            Black if needs_number => words.push(format!("{}...", fullmove)),
+            Black => {}
+        }
+        words.push(pgn_move.san.clone());
+        needs_number = false;
+
+        for nag in &pgn_move.nags {
+            words.push(format!("${}", nag));
+        }
+        if let Some(comment) = &pgn_move.comment {
+            write_comment(words, comment);
+            needs_number = true;
+        }
+
+        for variation in &pgn_move.variations {
+            let mut variation_words = Vec::new();
+            write_line(&mut variation_words, variation, fullmove, turn);
+            if let (Some(first), Some(last)) = (variation_words.first().cloned(), variation_words.last().cloned()) {
+                let count = variation_words.len();
+                variation_words[0] = format!("({}", first);
+                variation_words[count - 1] = if count == 1 { format!("({})", first) } else { format!("{})", last) };
+                words.extend(variation_words);
+                needs_number = true;
+            }
+        }
+
+        if turn == Black {
+            fullmove += 1;
+        }
+        turn = !turn;
+    }
+}
+
+// Comments are split into words so that long ones can be wrapped
+fn write_comment(words: &mut Vec<String>, comment: &str) {
+    let mut comment_words: Vec<String> = comment.split_whitespace().map(|v| v.to_string()).collect();
+    if comment_words.is_empty() {
+        comment_words.push(String::new());
+    }
+    let count = comment_words.len();
+    comment_words[0].insert(0, '{');
+    comment_words[count - 1].push('}');
+    words.extend(comment_words);
+}
+
+// Reads the moves of a line until the end of the game, or the end of the variation if depth > 0
+fn parse_line(tokens: &[Token], position: &mut usize, depth: u32) -> Result<Vec<PgnMove>, String> {
+    let mut line: Vec<PgnMove> = Vec::new();
+    let mut comment_before: Option<String> = None;
+
+    while let Some(token) = tokens.get(*position) {
+        match token {
+            Token::Move(san) => {
+                let mut pgn_move = PgnMove::new(san);
+                pgn_move.comment_before = comment_before.take();
+                line.push(pgn_move);
+            }
+            Token::Nag(nag) => match line.last_mut() {
+                Some(last) => last.nags.push(*nag),
+                None => return Err(format!("Annotation ${} is not after a move", nag))
+            },
+            Token::Comment(comment) => {
+                let existing = match line.last_mut() {
+                    Some(last) => &mut last.comment,
+                    None => &mut comment_before
+                };
+                match existing {
+                    Some(existing) => {
+                        existing.push(' ');
+                        existing.push_str(comment);
+                    }
+                    None => *existing = Some(comment.clone())
+                }
+            }
+            Token::VariationStart => {
+                *position += 1;
+                let variation = parse_line(tokens, position, depth + 1)?;
+                if tokens.get(*position) != Some(&Token::VariationEnd) {
+                    return Err("Variation is missing a closing ')'".to_string());
+                }
+                match line.last_mut() {
+                    Some(last) if !variation.is_empty() => last.variations.push(variation),
+                    Some(_) => {}
+                    None => return Err("Variation is not after a move".to_string())
+                }
+            }
+            Token::VariationEnd if depth > 0 => return Ok(line),
+            Token::VariationEnd => return Err("Unexpected ')' outside of a variation".to_string()),
+            // The end of a game, or the tags of the next one if the result was left off
+            Token::Result(_) | Token::Tag(..) if depth == 0 => return Ok(line),
+            Token::Result(result) => return Err(format!("Result {} inside a variation", result)),
+            Token::Tag(name, _) => return Err(format!("Tag {} inside a variation", name)),
+        }
+        *position += 1;
+    }
+
+    if depth > 0 {
+        return Err("Variation is missing a closing ')'".to_string());
+    }
+    Ok(line)
+}
+
+fn tokenize(pgn: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = pgn.chars().collect();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    let mut line_number = 1;
+
+    // Reads characters up to and not including the end character, or returns None at the end of the text
+    let read_until = |position: &mut usize, line_number: &mut u32, end: char| -> Option<String> {
+        let start = *position;
+        while *position < chars.len() && chars[*position] != end {
+            if chars[*position] == '\n' {
+                *line_number += 1;
+            }
+            *position += 1;
+        }
+        let text = (*position < chars.len()).then(|| chars[start..*position].iter().collect());
+        *position += 1;
+        text
+    };
+
+    while position < chars.len() {
+        let char = chars[position];
+        let at_line_start = position == 0 || chars[position - 1] == '\n';
+
+        match char {
+            '\n' => {
+                line_number += 1;
+                position += 1;
+            }
+            _ if char.is_whitespace() => position += 1,
+            // Escaped lines are for other programs to use
+            '%' if at_line_start => {
+                read_until(&mut position, &mut line_number, '\n');
+                line_number += 1;
+            }
+            '[' => {
+                let start_line = line_number;
+                position += 1;
+                let tag = read_tag(&chars, &mut position)
+                    .ok_or_else(|| format!("Line {}: Tag should look like [Name \"Value\"]", start_line))?;
+                tokens.push(tag);
+            }
+            '{' => {
+                let start_line = line_number;
+                position += 1;
+                let comment = read_until(&mut position, &mut line_number, '}')
+                    .ok_or_else(|| format!("Line {}: Comment is missing a closing '}}'", start_line))?;
+                tokens.push(Token::Comment(comment.split_whitespace().collect::<Vec<&str>>().join(" ")));
+            }
+            // Runs to the end of the line, or the end of the text on the last line
+            ';' => {
+                position += 1;
+                let start = position;
+                let comment = read_until(&mut position, &mut line_number, '\n')
+                    .unwrap_or_else(|| chars[start..].iter().collect());
+                tokens.push(Token::Comment(comment.trim().to_string()));
+                line_number += 1;
+            }
+            '(' => {
+                tokens.push(Token::VariationStart);
+                position += 1;
+            }
+            ')' => {
+                tokens.push(Token::VariationEnd);
+                position += 1;
+            }
+            '$' => {
+                position += 1;
+                let start = position;
+                while position < chars.len() && chars[position].is_ascii_digit() {
+                    position += 1;
+                }
+                let nag: String = chars[start..position].iter().collect();
+                let nag = nag.parse().map_err(|_| format!("Line {}: Invalid annotation ${}", line_number, nag))?;
+                tokens.push(Token::Nag(nag));
+            }
+            '!' | '?' => {
+                let start = position;
+                while position < chars.len() && (chars[position] == '!' || chars[position] == '?') {
+                    position += 1;
+                }
+                let suffix: String = chars[start..position].iter().collect();
+                let nag = match suffix.as_str() {
+                    "!" => 1,
+                    "?" => 2,
+                    "!!" => 3,
+                    "??" => 4,
+                    "!?" => 5,
+                    "?!" => 6,
+                    _ => return Err(format!("Line {}: Invalid annotation {}", line_number, suffix))
+                };
+                tokens.push(Token::Nag(nag));
+            }
+            '*' => {
+                tokens.push(Token::Result("*".to_string()));
+                position += 1;
+            }
+            _ if char.is_ascii_alphanumeric() => {
+                let start = position;
+                while position < chars.len()
+                    && (chars[position].is_ascii_alphanumeric() || "+#=:/-.".contains(chars[position])) {
+                    position += 1;
+                }
+                let symbol: String = chars[start..position].iter().collect();
+                if let Some(token) = read_symbol(&symbol) {
+                    tokens.push(token);
+                }
+            }
+            _ => return Err(format!("Line {}: Unexpected character '{}'", line_number, char))
+        }
+    }
+
+    Ok(tokens)
+}
+
+// Tag pairs are [Name "Value"], where the value can have \" and \\ in it
+fn read_tag(chars: &[char], position: &mut usize) -> Option<Token> {
+    let skip_whitespace = |position: &mut usize| {
+        while *position < chars.len() && chars[*position].is_whitespace() && chars[*position] != '\n' {
+            *position += 1;
+        }
+    };
+
+    skip_whitespace(position);
+    let start = *position;
+    while *position < chars.len() && (chars[*position].is_ascii_alphanumeric() || chars[*position] == '_') {
+        *position += 1;
+    }
+    let name: String = chars[start..*position].iter().collect();
+
+    skip_whitespace(position);
+    if name.is_empty() || chars.get(*position) != Some(&'"') {
+        return None;
+    }
+    *position += 1;
+
+    let mut value = String::new();
+    loop {
+        match chars.get(*position)? {
+            '"' => break,
+            '\\' => {
+                *position += 1;
+                value.push(*chars.get(*position)?);
+            }
+            '\n' => return None,
+            char => value.push(*char)
+        }
+        *position += 1;
+    }
+    *position += 1;
+
+    skip_whitespace(position);
+    if chars.get(*position) != Some(&']') {
+        return None;
+    }
+    *position += 1;
+
+    Some(Token::Tag(name, value))
+}
+
+// Move numbers are skipped, and may be written right up against the move, e.g. 1.e4
+fn read_symbol(symbol: &str) -> Option<Token> {
+    if ["1-0", "0-1", "1/2-1/2"].contains(&symbol) {
+        return Some(Token::Result(symbol.to_string()));
+    }
+
+    let san = symbol.trim_start_matches(|v: char| v.is_ascii_digit());
+    if san.len() < symbol.len() && san.starts_with('.') {
+        let san = san.trim_start_matches('.');
+        return (!san.is_empty()).then(|| Token::Move(san.to_string()));
+    }
+
+    Some(Token::Move(symbol.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Board, PgnGame, PgnMove};
+
+    const GAME: &str = r#"[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1/2-1/2"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 {This opening is called the Ruy Lopez.} 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3
+O-O 9. h3 Nb8 10. d4 Nbd7 11. c4 c6 12. cxb5 axb5 13. Nc3 Bb7 14. Bg5 b4 15.
+Nb1 h6 16. Bh4 c5 17. dxe5 Nxe4 18. Bxe7 Qxe7 19. exd6 Qf6 20. Nbd2 Nxd6 21.
+Nc4 Nxc4 22. Bxc4 Nb6 23. Ne5 Rae8 24. Bxf7+ Rxf7 25. Nxf7 Rxe1+ 26. Qxe1 Kxf7
+27. Qe3 Qg5 28. Qxg5 hxg5 29. b3 Ke6 30. a3 Kd6 31. axb4 cxb4 32. Ra5 Nd5 33.
+f3 Bc8 34. Kf2 Bf5 35. Ra7 g6 36. Ra6+ Kc5 37. Ke1 Nf4 38. g3 Nxh3 39. Kd2 Kb5
+40. Rd6 Kc5 41. Ra6 Nf2 42. g4 Bd3 43. Re6 1/2-1/2
+"#;
+
+    const ANNOTATED: &str = r#"[Event "Annotated"]
+[Annotator "Someone \"Quoted\""]
+
+{Starting comment} 1. e4! e5 (1... c5 $14 {Sicilian} 2. Nf3 (2. c3 d5) 2... d6) (1... e6?! ; French
+) 2. Nf3 Nc6!? 3. Bc4 $1 *"#;
+
+    #[test]
+    fn parse_game() {
+        let game = PgnGame::parse(GAME).unwrap();
+        assert_eq!(game.tag("White"), Some("Fischer, Robert J."));
+        assert_eq!(game.tag("Site"), Some("Belgrade, Serbia JUG"));
+        assert_eq!(game.result, "1/2-1/2");
+        assert_eq!(game.moves.len(), 85);
+        assert_eq!(game.moves[5].comment.as_deref(), Some("This opening is called the Ruy Lopez."));
+
+        assert_eq!(game.replay().unwrap().to_fen(), "8/8/4R1p1/2k3p1/1p4P1/1P1b1P2/3K1n2/8 b - - 2 43");
+    }
+
+    #[test]
+    fn parse_annotations() {
+        let game = PgnGame::parse(ANNOTATED).unwrap();
+        assert_eq!(game.tag("Annotator"), Some("Someone \"Quoted\""));
+        assert_eq!(game.result, "*");
+
+        let e4 = &game.moves[0];
+        assert_eq!(e4.comment_before.as_deref(), Some("Starting comment"));
+        assert_eq!(e4.nags, vec![1]);
+
+        let e5 = &game.moves[1];
+        assert_eq!(e5.variations.len(), 2);
+        let sicilian = &e5.variations[0];
+        assert_eq!(sicilian.iter().map(|v| v.san.as_str()).collect::<Vec<&str>>(), vec!["c5", "Nf3", "d6"]);
+        assert_eq!(sicilian[0].nags, vec![14]);
+        assert_eq!(sicilian[0].comment.as_deref(), Some("Sicilian"));
+        assert_eq!(sicilian[1].variations, vec![vec![PgnMove::new("c3"), PgnMove::new("d5")]]);
+        assert_eq!(e5.variations[1][0].nags, vec![6]);
+        assert_eq!(e5.variations[1][0].comment.as_deref(), Some("French"));
+
+        assert_eq!(game.moves[3].nags, vec![5]);
+        assert_eq!(game.main_line().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn write_game() {
+        let game = PgnGame::parse(ANNOTATED).unwrap();
+        assert_eq!(game.to_string(), r#"[Event "Annotated"]
+[Site "?"]
+[Date "????.??.??"]
+[Round "?"]
+[White "?"]
+[Black "?"]
+[Result "*"]
+[Annotator "Someone \"Quoted\""]
+
+{Starting comment} 1. e4 $1 e5 (1... c5 $14 {Sicilian} 2. Nf3 (2. c3 d5) 2...
+d6) (1... e6 $6 {French}) 2. Nf3 Nc6 $5 3. Bc4 $1 *
+"#);
+
+        for pgn in [GAME, ANNOTATED] {
+            let game = PgnGame::parse(pgn).unwrap();
+            let written = game.to_string();
+            assert!(written.lines().all(|v| v.len() < 80));
+            assert_eq!(PgnGame::parse(&written).unwrap().moves, game.moves);
+        }
+    }
+
+    #[test]
+    fn from_moves() {
+        let mut board = Board::new();
+        let mut moves = Vec::new();
+        for san in ["f3", "e5", "g4", "Qh4#"] {
+            let _move = board.parse_san(san).unwrap();
+            board.move_piece(_move).unwrap();
+            moves.push(_move);
+        }
+
+        let game = PgnGame::from_moves(&Board::new(), &moves).unwrap();
+        assert_eq!(game.result, "0-1");
+        assert!(game.to_string().ends_with("\n\n1. f3 e5 2. g4 Qh4# 0-1\n"));
+
+        // Games from other positions keep track of where they started
+        let start = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 30").unwrap();
+        let game = PgnGame::from_moves(&start, &[start.parse_san("Kd7").unwrap()]).unwrap();
+        assert!(game.to_string().contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 30\"]\n\n30... Kd7 *\n"));
+        assert_eq!(PgnGame::parse(&game.to_string()).unwrap().replay().unwrap().to_fen(), "8/3k4/8/8/8/8/4P3/4K3 w - - 1 31");
+    }
+
+    #[test]
+    fn multiple_games() {
+        let games = PgnGame::parse_all(&format!("{}\n{}", GAME, ANNOTATED)).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[1].tag("Event"), Some("Annotated"));
+        assert!(PgnGame::parse(&format!("{}\n{}", GAME, ANNOTATED)).is_err());
+
+        // Games missing a result are ended by the tags of the next game
+        let games = PgnGame::parse_all("[Result \"1-0\"]\n1. e4\n[Event \"Next\"]\n1.d4 *").unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].result, "1-0");
+        assert_eq!(games[1].moves[0].san, "d4");
+    }
+
+    #[test]
+    fn errors() {
+        let error = |pgn: &str| PgnGame::parse(pgn).and_then(|v| v.main_line()).unwrap_err();
+
+        assert_eq!(error("1. e4 e5 2. Ke3 *"), "Illegal move 2. Ke3: No King can move to e3");
+        assert_eq!(error("1. e4 e5 (1... e4) *"), "Illegal move 1... e4: No Pawn can move to e4");
+        assert_eq!(error("1. e4 (1. d4"), "Variation is missing a closing ')'");
+        assert_eq!(error("1. e4 (1. d4 *"), "Result * inside a variation");
+        assert_eq!(error("1. e4 ) *"), "Unexpected ')' outside of a variation");
+        assert_eq!(error("1. e4 {unfinished"), "Line 1: Comment is missing a closing '}'");
+        assert_eq!(error("[Event \"x\" 1. e4"), "Line 1: Tag should look like [Name \"Value\"]");
+        assert_eq!(error("[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"] *"), "Invalid FEN tag: White should have exactly 1 king, found 0");
+    }
+}
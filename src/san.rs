@@ -0,0 +1,312 @@
+use crate::*;
+
+impl Board {
+    // Finds the legal move written in Standard Algebraic Notation, e.g. e4, Nbd7, exd5, e8=Q+ or O-O-O.
+    // Check and annotation marks on the end are allowed but not required.
+    pub fn parse_san(&self, san: &str) -> Result<Move, MoveError> {
+        let san = san.trim();
+        let trimmed = san.trim_end_matches(['+', '#', '!', '?']);
+
+        let legal_moves = self.legal_moves();
+
+        // Zeros are not correct SAN, but are common enough to accept
+        let castle = match trimmed {
+            "O-O" | "0-0" => Some(CastleSide::King),
+            "O-O-O" | "0-0-0" => Some(CastleSide::Queen),
+            _ => None
+        };
+        if let Some(side) = castle {
+            let column = match side {
+                CastleSide::King => 6,
+                CastleSide::Queen => 2,
+            };
+            return legal_moves
+                .into_iter()
+                .find(|v| Board::castle_rook_move(*v).is_some() && v.end.coord.column == column)
+                .ok_or(MoveError::CannotCastle(side));
+        }
+
+        // The part of the move still to be read is chars[first..last]
+        let chars: Vec<char> = trimmed.chars().collect();
+        let mut first = 0;
+        let mut last = chars.len();
+
+        // Promotions are normally written e8=Q, but e8Q is also seen
+        let promotion = match chars.last() {
+            Some(char) if chars.len() > 2 && char.is_ascii_alphabetic() => {
+                let piece = Piece::promotion_from_char(char.to_ascii_lowercase())
+                    .ok_or_else(|| MoveError::notation(san, last - 1, "Invalid promotion piece"))?;
+                last -= 1;
+                if chars[last - 1] == '=' {
+                    last -= 1;
+                }
+                Some(piece)
+            }
+            _ => None
+        };
+
+        let variant = match chars.first().and_then(|v| piece_from_san(*v)) {
+            Some(variant) => {
+                first += 1;
+                variant
+            }
+            None => Pawn
+        };
+
+        if last < first + 2 {
+            return Err(MoveError::notation(san, last, "Missing the square the piece moves to"));
+        }
+        last -= 2;
+        let end = parse_square_at(san, last)?;
+
+        let capture = last > first && chars[last - 1] == 'x';
+        if capture {
+            last -= 1;
+        }
+
+        // Whatever is left says which file and/or rank the piece is moving from
+        let mut from_file = None;
+        let mut from_rank = None;
+        for (position, char) in chars.iter().enumerate().take(last).skip(first) {
+            match char {
+                'a'..='h' if from_file.is_none() => from_file = Some(*char),
+                '1'..='8' if from_rank.is_none() => from_rank = char.to_digit(10),
+                _ => return Err(MoveError::notation(san, position, "Expected the file or rank the piece is moving from"))
+            }
+        }
+
+        let matches: Vec<Move> = legal_moves
+            .into_iter()
+            .filter(|v| {
+                v.piece.variant == variant
+                    && v.end == end
+                    && from_file.is_none_or(|file| v.start.file() == file)
+                    && from_rank.is_none_or(|rank| v.start.rank() == rank)
+                    && (v.promotion.is_none() || v.promotion == Some(promotion.unwrap_or(Queen)))
+            })
+            .collect();
+
+        let _move = match matches.len() {
+            0 => return Err(MoveError::CannotReach { piece: variant, start: None, end }),
+            1 => matches[0],
+            _ => return Err(MoveError::Ambiguous(matches.iter().map(|v| v.start).collect())),
+        };
+
+        if promotion.is_some() && _move.promotion.is_none() {
+            return Err(MoveError::InvalidPromotion);
+        }
+        if capture && !self.is_capture(_move) {
+            return Err(MoveError::notation(san, last, "Move is not a capture"));
+        }
+
+        Ok(_move)
+    }
+
+    // Reads a move typed at the terminal prompt. Anything that cannot be read as SAN may still be in
+    // the older nf3 / ng1f3 syntax, but a SAN move that was read and is not possible is reported as it is.
+    // The exception is a lowercase b, which SAN reads as the b file but the older syntax uses for a
+    // bishop, e.g. bc4.
+    pub fn parse_typed_move(&self, text: &str) -> Result<Move, MoveError> {
+        let text = text.trim();
+        match self.parse_san(text) {
+            Err(err @ MoveError::Notation { .. }) => parse_str_move(text, self).map_err(|_| err),
+            Err(err) if text.starts_with('b') => parse_str_move(text, self).map_err(|_| err),
+            parsed => parsed
+        }
+    }
+
+    // Standard Algebraic Notation for a legal move, with the least disambiguation needed and a + or #
+    // on the end if it gives check or checkmate
+    pub fn to_san(&self, _move: Move) -> String {
+        let mut san = String::new();
+
+        match Board::castle_rook_move(_move) {
+            Some(_) if _move.end.coord.column == 6 => san.push_str("O-O"),
+            Some(_) => san.push_str("O-O-O"),
+            None => {
+                if _move.piece.variant == Pawn {
+                    // Pawn captures always say which file the pawn came from
+                    if self.is_capture(_move) {
+                        san.push(_move.start.file());
+                    }
+                } else {
+                    san.push(_move.piece.variant.to_char().to_ascii_uppercase());
+
+                    let others: Vec<Move> = self.legal_moves()
+                        .into_iter()
+                        .filter(|v| v.piece == _move.piece && v.end == _move.end && v.start != _move.start)
+                        .collect();
+                    if !others.is_empty() {
+                        let start = _move.start;
+                        if others.iter().all(|v| v.start.file() != start.file()) {
+                            san.push(start.file());
+                        } else if others.iter().all(|v| v.start.rank() != start.rank()) {
+                            san.push_str(&start.rank().to_string());
+                        } else {
+                            san.push_str(&start.to_string());
+                        }
+                    }
+                }
+
+                if self.is_capture(_move) {
+                    san.push('x');
+                }
+                san.push_str(&_move.end.to_string());
+
+                if let Some(promotion) = _move.promotion {
+                    san.push('=');
+                    san.push(promotion.to_char().to_ascii_uppercase());
+                }
+            }
+        }
+
+        let mut after = self.clone();
+        after.make_move(_move);
+        if after.in_check() {
+            san.push(if after.legal_moves().is_empty() { '#' } else { '+' });
+        }
+
+        san
+    }
+
+    // SAN of each move in a line played from this position, e.g. the principal variation of a search
+    pub fn to_san_line(&self, line: &[Move]) -> Vec<String> {
+        let mut board = self.clone();
+        line.iter()
+            .map(|_move| {
+                let san = board.to_san(*_move);
+                board.make_move(*_move);
+                san
+            })
+            .collect()
+    }
+
+    // Whether the move takes a piece, including en passant
+    pub fn is_capture(&self, _move: Move) -> bool {
+        self.pieces[_move.end.index as usize] != Empty || self.is_en_passant(_move)
+    }
+}
+
+// SAN uses uppercase letters for pieces, and no letter for pawns
+fn piece_from_san(char: char) -> Option<Piece> {
+    match char {
+        'N' => Some(Knight),
+        'B' => Some(Bishop),
+        'R' => Some(Rook),
+        'Q' => Some(Queen),
+        'K' => Some(King),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Board, START_FEN};
+
+    // Plays the SAN moves in order, checking that each one is written back the same way
+    fn play(fen: &str, moves: &[&str]) -> Board {
+        let mut board = Board::from_fen(fen).unwrap();
+        for san in moves {
+            let _move = board.parse_san(san).unwrap();
+            assert_eq!(board.to_san(_move), *san);
+            board.move_piece(_move).unwrap();
+        }
+        board
+    }
+
+    #[test]
+    fn games() {
+        play(START_FEN, &["e4", "e5", "Bc4", "Nc6", "Qh5", "Nf6", "Qxf7#"]);
+        play(START_FEN, &["e4", "d5", "exd5", "Qxd5", "Nc3", "Qa5", "d4", "c6", "Nf3", "Bg4", "Bf4", "e6", "h3", "Bxf3", "Qxf3", "Bb4", "Be2", "Nd7", "a3", "O-O-O"]);
+        // En passant
+        play("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", &["exf6", "Nxf6", "Nf3", "e6", "Be2", "Bd6", "O-O", "O-O"]);
+    }
+
+    #[test]
+    fn lines() {
+        let board = Board::new();
+        let mut after = board.clone();
+        let mut line = Vec::new();
+        for san in ["e4", "d5", "exd5", "Qxd5"] {
+            let _move = after.parse_san(san).unwrap();
+            after.make_move(_move);
+            line.push(_move);
+        }
+        assert_eq!(board.to_san_line(&line), vec!["e4", "d5", "exd5", "Qxd5"]);
+    }
+
+    #[test]
+    fn disambiguation() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/R4RK1 w - - 0 1").unwrap();
+        assert_eq!(board.to_san(board.parse_san("Rad1").unwrap()), "Rad1");
+        assert_eq!(board.to_san(board.parse_san("Rfd1").unwrap()), "Rfd1");
+        assert!(board.parse_san("Rd1").is_err());
+
+        let board = Board::from_fen("4k3/8/8/8/R7/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(board.to_san(board.parse_san("R4a2").unwrap()), "R4a2");
+        assert_eq!(board.to_san(board.parse_san("R1a2").unwrap()), "R1a2");
+
+        // Three queens that can all reach d4 need both the file and the rank for one of them
+        let board = Board::from_fen("8/7k/8/8/Q7/8/8/Q2Q3K w - - 0 1").unwrap();
+        assert_eq!(board.to_san(board.parse_san("Qa1d4").unwrap()), "Qa1d4");
+        assert_eq!(board.to_san(board.parse_san("Qdd4").unwrap()), "Qdd4");
+        assert_eq!(board.to_san(board.parse_san("Q4d4").unwrap()), "Q4d4");
+        assert!(board.parse_san("Qad4").is_err());
+
+        // A pinned knight does not count as another knight that can move there
+        let board = Board::from_fen("4k3/8/8/8/8/6N1/8/r2NK3 w - - 0 1").unwrap();
+        assert_eq!(board.to_san(board.parse_san("Ne2").unwrap()), "Ne2");
+    }
+
+    #[test]
+    fn promotions() {
+        let board = Board::from_fen("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.to_san(board.parse_san("a8=Q").unwrap()), "a8=Q");
+        assert_eq!(board.to_san(board.parse_san("axb8=Q+").unwrap()), "axb8=Q+");
+        assert_eq!(board.to_san(board.parse_san("axb8=N").unwrap()), "axb8=N");
+        assert_eq!(board.to_san(board.parse_san("axb8R").unwrap()), "axb8=R+");
+        // Queen by default
+        assert_eq!(board.parse_san("a8").unwrap().promotion, Some(crate::Queen));
+    }
+
+    #[test]
+    fn every_legal_move() {
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        for _move in board.legal_moves() {
+            assert_eq!(board.parse_san(&board.to_san(_move)), Ok(_move));
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let board = Board::new();
+        assert!(board.parse_san("e5").is_err());
+        assert!(board.parse_san("Nf4").is_err());
+        assert!(board.parse_san("O-O").is_err());
+        assert!(board.parse_san("exd3").is_err());
+        assert!(board.parse_san("Nxf3").is_err());
+        assert!(board.parse_san("e4=Q").is_err());
+        assert!(board.parse_san("Zf3").is_err());
+        assert!(board.parse_san("").is_err());
+    }
+
+    #[test]
+    fn typed_moves() {
+        let board = Board::new();
+        assert_eq!(board.to_san(board.parse_typed_move(" Nf3\n").unwrap()), "Nf3");
+        assert_eq!(board.to_san(board.parse_typed_move("e4").unwrap()), "e4");
+        // The older syntax is still understood
+        assert_eq!(board.to_san(board.parse_typed_move("ng1f3").unwrap()), "Nf3");
+        assert_eq!(board.to_san(board.parse_typed_move("pe4").unwrap()), "e4");
+        // Including bishops, whose b is also a file letter
+        let board = Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2").unwrap();
+        assert_eq!(board.to_san(board.parse_typed_move("bc4").unwrap()), "Bc4");
+        assert_eq!(board.to_san(board.parse_typed_move("bf1c4").unwrap()), "Bc4");
+        assert_eq!(board.to_san(board.parse_typed_move("b3").unwrap()), "b3");
+        assert!(matches!(board.parse_typed_move("b5"), Err(crate::MoveError::CannotReach { piece: crate::Pawn, .. })));
+        let board = Board::new();
+        // SAN that was read but is not possible is not hidden by the older syntax
+        assert!(matches!(board.parse_typed_move("Nf4"), Err(crate::MoveError::CannotReach { .. })));
+        assert!(matches!(board.parse_typed_move("zz"), Err(crate::MoveError::Notation { .. })));
+    }
+}
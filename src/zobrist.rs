@@ -0,0 +1,186 @@
+use std::hash::{Hash, Hasher};
+use crate::*;
+
+// Zobrist hashing gives every feature of a position its own random number, and the key of a
+// position is all of its features' numbers xored together. Moving a piece then only needs the
+// numbers for the squares it left and arrived on to be xored in to keep the key up to date.
+
+// Indexed by colour, then piece, then square
+const PIECES: [[[u64; 64]; 6]; 2] = piece_table();
+// White king side, white queen side, black king side, black queen side
+const CASTLING: [u64; 4] = [random(768), random(769), random(770), random(771)];
+// Indexed by the column of the en passant square
+const EN_PASSANT: [u64; 8] = en_passant_table();
+const BLACK_TO_MOVE: u64 = random(780);
+
+// SplitMix64, so the numbers are fixed at compile time and keys are the same between runs
+const fn random(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+const fn piece_table() -> [[[u64; 64]; 6]; 2] {
+    let mut table = [[[0; 64]; 6]; 2];
+    let mut colour = 0;
+    while colour < 2 {
+        let mut piece = 0;
+        while piece < 6 {
+            let mut index = 0;
+            while index < 64 {
+                table[colour][piece][index] = random(((colour * 6 + piece) * 64 + index) as u64);
+                index += 1;
+            }
+            piece += 1;
+        }
+        colour += 1;
+    }
+    table
+}
+
+const fn en_passant_table() -> [u64; 8] {
+    let mut table = [0; 8];
+    let mut column = 0;
+    while column < 8 {
+        table[column] = random(772 + column as u64);
+        column += 1;
+    }
+    table
+}
+
+pub(crate) fn piece_key(piece: ColourPiece, index: i32) -> u64 {
+    PIECES[piece.colour.index()][piece.variant.index()][index as usize]
+}
+
+pub(crate) fn castling_key(castling: CastlingRights) -> u64 {
+    [castling.white_king_side, castling.white_queen_side, castling.black_king_side, castling.black_queen_side]
+        .iter()
+        .zip(CASTLING)
+        .filter(|(has, _)| **has)
+        .fold(0, |key, (_, v)| key ^ v)
+}
+
+impl Board {
+    // Zobrist key of the position, which is the same for any two positions with the same pieces on
+    // the same squares, side to move, castling rights and en passant options, however they were reached.
+    pub fn key(&self) -> u64 {
+        // turn is a public field, so it is added here rather than kept up to date in the key
+        match self.turn {
+            White => self.key,
+            Black => self.key ^ BLACK_TO_MOVE,
+        }
+    }
+
+    // The en passant square only counts if a pawn is actually able to take on it, so that positions
+    // that only differ by an en passant capture that cannot happen are still the same
+    pub(crate) fn en_passant_key(&self) -> u64 {
+        match self.en_passant {
+            Some(square) if PAWN_ATTACKS[(!self.turn).index()][square.index as usize] & self.piece_bb(self.turn, Pawn) != 0 => {
+                EN_PASSANT[square.coord.column as usize]
+            }
+            _ => 0
+        }
+    }
+
+    // Works the key out from nothing, rather than from the last one. Does not include the side to move.
+    pub(crate) fn compute_key(&self) -> u64 {
+        let mut key = castling_key(self.castling) ^ self.en_passant_key();
+        for (index, space) in self.pieces.iter().enumerate() {
+            if let Full(piece) = space {
+                key ^= piece_key(*piece, index as i32);
+            }
+        }
+        key
+    }
+}
+
+// Boards are equal when they hold the same position, even if the move clocks or the moves
+// played to reach it are different
+impl PartialEq for Board {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+            && self.pieces == other.pieces
+            && self.turn == other.turn
+            && self.castling == other.castling
+    }
+}
+
+impl Eq for Board {}
+
+impl Hash for Board {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.key());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use crate::{Board, START_FEN};
+
+    // Checks the key kept up to date by make_move and unmake_move against one worked out from nothing,
+    // for every position down to the given depth
+    fn check_keys(board: &mut Board, depth: u32) {
+        assert_eq!(board.key, board.compute_key(), "{}", board.to_fen());
+        if depth == 0 {
+            return;
+        }
+
+        let before = board.key();
+        for _move in board.legal_moves() {
+            let undo = board.make_move(_move);
+            check_keys(board, depth - 1);
+            board.unmake_move(_move, undo);
+            assert_eq!(board.key(), before);
+        }
+    }
+
+    #[test]
+    fn incremental() {
+        check_keys(&mut Board::new(), 3);
+        // Castling, en passant and promotions
+        check_keys(&mut Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap(), 2);
+        check_keys(&mut Board::from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8").unwrap(), 2);
+        check_keys(&mut Board::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap(), 3);
+    }
+
+    #[test]
+    fn transpositions() {
+        let play = |moves: &[&str]| {
+            let mut board = Board::new();
+            for san in moves {
+                board.move_piece(board.parse_san(san).unwrap()).unwrap();
+            }
+            board
+        };
+
+        let first = play(&["Nf3", "Nf6", "Nc3"]);
+        let second = play(&["Nc3", "Nf6", "Nf3"]);
+        assert_eq!(first.key(), second.key());
+        assert_eq!(first, second);
+
+        // Same pieces, different side to move
+        assert_ne!(play(&["Nf3", "Nf6", "Ng1", "Ng8"]).key(), play(&["Nf3", "Nf6", "Ng1"]).key());
+        // Returning to the start position, but with the move clocks changed
+        assert_eq!(play(&["Nf3", "Nf6", "Ng1", "Ng8"]), Board::new());
+        // Rooks back on their squares, but without the right to castle
+        assert_ne!(play(&["Nf3", "Nf6", "Rg1", "Rg8", "Rh1", "Rh8", "Ng1", "Ng8"]), Board::new());
+
+        let mut positions = HashSet::new();
+        positions.insert(first);
+        assert!(positions.contains(&second));
+        assert!(!positions.contains(&Board::new()));
+    }
+
+    #[test]
+    fn en_passant() {
+        // e4 leaves an en passant square, but no black pawn can take on it
+        let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+        assert_eq!(board, Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap());
+
+        let board = Board::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+        assert_ne!(board, Board::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap());
+        assert_ne!(Board::new().key(), Board::from_fen(START_FEN.replace(" w ", " b ").as_str()).unwrap().key());
+    }
+}